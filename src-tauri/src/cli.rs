@@ -0,0 +1,594 @@
+//! `pm <command>` — scripting client for the background daemon.
+//!
+//! Processes can be addressed by id or by unique name. With `--json`,
+//! results are printed as JSON (one object per line for `logs`).
+
+use crate::daemon::{self, DaemonClient};
+use crate::types::{ConfigBackup, ConfigIssue, ImportFormat, IssueSeverity, ImportReport, LogExportFormat, LogPage, LogQuery, ProcessState, WorkspaceInfo};
+use crate::workspace;
+use chrono::TimeZone;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const USAGE: &str = "\
+Usage: pm [--json] <command> [args]
+
+Commands:
+  list                          List processes and their status
+  start <process> [--with-deps] Start a process (and what it depends on)
+  stop <process>                Stop a process gracefully
+  restart <process>             Restart a process
+  remove <process>              Stop and remove a process
+  logs <process> [-f] [-n N] [--grep PATTERN]
+                                Print the last N log lines (default 100), -f to follow
+  export <process> [-o FILE] [--format text|jsonl|csv] [--since T] [--until T] [--stream S]
+                                Write a process's logs to a file
+  bundle [-o FILE] [--since T] [process]...
+                                Zip logs, configs, metrics and crashes for a bug report
+  add [--name N] [--cwd DIR] [--env K=V]... -- <cmd> [args]
+                                Add a process; it runs in DIR (default: the current directory)
+  import <file> [--format compose|procfile|pm2] [--run-js]
+                                Add processes from a docker-compose file, Procfile or PM2 ecosystem file
+                                (--run-js allows running a JavaScript ecosystem file with Node.js)
+  save [--force]                Save processes to the config file, --force to overwrite
+                                changes made to it by another instance
+  check                         Report problems in the config file, per process
+  backups                       List backups of the config file, newest first
+  restore <backup>              Restore the config file from a backup and apply it
+  up [dir|file|workspace]       Open a project's pm.toml (default: .) and start its processes
+  down [dir|file|workspace]     Stop a workspace's processes
+  close [dir|file|workspace]    Stop and unload a workspace
+  workspaces                    List open workspaces
+
+Run `pm --daemon` to start the supervisor in the foreground.";
+
+struct Cli {
+    client: DaemonClient,
+    json: bool,
+}
+
+/// Run a CLI invocation and return the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    // `--json` is ours only before `--`; everything after it is a child's argv.
+    let split = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let json = args[..split].iter().any(|a| a == "--json");
+    let args: Vec<&str> = args[..split]
+        .iter()
+        .map(String::as_str)
+        .filter(|a| *a != "--json")
+        .chain(args[split..].iter().map(String::as_str))
+        .collect();
+
+    let Some((&command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    if matches!(command, "help" | "--help" | "-h") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let Some(client) = daemon::connect_or_spawn() else {
+        eprintln!("pm: could not reach or start the daemon");
+        return 1;
+    };
+    let cli = Cli { client, json };
+
+    let result = match command {
+        "list" | "ls" => cli.list(),
+        "start" => cli.start(rest),
+        "stop" => cli.simple("stop_process", "Stopped", rest),
+        "restart" => cli.restart(rest),
+        "remove" | "rm" => cli.simple("remove_process", "Removed", rest),
+        "logs" => cli.logs(rest),
+        "export" => cli.export(rest),
+        "bundle" => cli.bundle(rest),
+        "add" => cli.add(rest),
+        "import" => cli.import(rest),
+        "save" => cli.save(rest),
+        "check" => cli.check(),
+        "backups" => cli.backups(),
+        "restore" => cli.restore(rest),
+        "up" => cli.up(rest),
+        "down" => cli.workspace("workspace_down", "Stopped", rest),
+        "close" => cli.workspace("close_workspace", "Closed", rest),
+        "workspaces" => cli.workspaces(),
+        _ => {
+            eprintln!("pm: unknown command '{}'\n\n{}", command, USAGE);
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            if json {
+                println!("{}", json!({ "error": e }));
+            } else {
+                eprintln!("pm: {}", e);
+            }
+            1
+        }
+    }
+}
+
+/// The single positional `<process>` argument.
+fn process_arg<'a>(args: &[&'a str]) -> Result<&'a str, String> {
+    let positional: Vec<&str> = args.iter().copied().filter(|a| !a.starts_with('-')).collect();
+    match positional.as_slice() {
+        [reference] => Ok(reference),
+        [] => Err("missing <process> argument".to_string()),
+        _ => Err("expected a single <process> argument".to_string()),
+    }
+}
+
+/// The optional single positional argument.
+fn optional_arg<'a>(args: &[&'a str]) -> Result<Option<&'a str>, String> {
+    match args {
+        [] => Ok(None),
+        [arg] => Ok(Some(arg)),
+        _ => Err("expected at most one argument".to_string()),
+    }
+}
+
+/// The daemon has its own working directory, so hand it absolute paths.
+fn absolute_path(path: &str) -> Result<String, String> {
+    let path = std::path::Path::new(path);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().map_err(|e| e.to_string())?.join(path)
+    };
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn format_uptime(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+impl Cli {
+    fn call<T: serde::de::DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        self.client.call_blocking(method, params)
+    }
+
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        self.call("resolve_process", json!({ "process_id": reference }))
+    }
+
+    fn list(&self) -> Result<(), String> {
+        let mut processes: Vec<ProcessState> = self.call("get_processes", json!({}))?;
+        processes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if self.json {
+            println!("{}", serde_json::to_string(&processes).map_err(|e| e.to_string())?);
+            return Ok(());
+        }
+
+        let width = processes.iter().map(|p| p.name.len()).max().unwrap_or(0).max(4);
+        println!("{:<width$}  {:<8}  {:>7}  {:>8}  {:>8}  ID", "NAME", "STATUS", "PID", "UPTIME", "RESTARTS");
+        for p in &processes {
+            let pid = p.pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string());
+            let uptime = if p.status == "Running" { format_uptime(p.uptime_ms) } else { "-".to_string() };
+            println!(
+                "{:<width$}  {:<8}  {:>7}  {:>8}  {:>8}  {}",
+                p.name, p.status, pid, uptime, p.crash_count, p.id
+            );
+        }
+        Ok(())
+    }
+
+    fn start(&self, args: &[&str]) -> Result<(), String> {
+        let reference = process_arg(args)?;
+        let with_dependencies = args.contains(&"--with-deps");
+        let id = self.resolve(reference)?;
+        let pid: u32 = self.call(
+            "start_process",
+            json!({ "process_id": id, "with_dependencies": with_dependencies }),
+        )?;
+        if self.json {
+            println!("{}", json!({ "id": id, "pid": pid }));
+        } else {
+            println!("Started {} (pid {})", reference, pid);
+        }
+        Ok(())
+    }
+
+    fn restart(&self, args: &[&str]) -> Result<(), String> {
+        let reference = process_arg(args)?;
+        let id = self.resolve(reference)?;
+        let pid: u32 = self.call("restart_process", json!({ "process_id": id }))?;
+        if self.json {
+            println!("{}", json!({ "id": id, "pid": pid }));
+        } else {
+            println!("Restarted {} (pid {})", reference, pid);
+        }
+        Ok(())
+    }
+
+    /// Commands that take a process and return nothing.
+    fn simple(&self, method: &str, verb: &str, args: &[&str]) -> Result<(), String> {
+        let reference = process_arg(args)?;
+        let id = self.resolve(reference)?;
+        self.call::<()>(method, json!({ "process_id": id }))?;
+        if self.json {
+            println!("{}", json!({ "id": id }));
+        } else {
+            println!("{} {}", verb, reference);
+        }
+        Ok(())
+    }
+
+    fn print_log(&self, entry: &Value) {
+        if self.json {
+            println!("{}", entry);
+            return;
+        }
+        let field = |key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default();
+        let line = format!("{} {}", field("timestamp"), field("message"));
+        if field("stream") == "stderr" {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    fn logs(&self, args: &[&str]) -> Result<(), String> {
+        let mut follow = false;
+        let mut query = LogQuery {
+            limit: Some(100),
+            ..LogQuery::default()
+        };
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(&arg) = iter.next() {
+            match arg {
+                "-f" | "--follow" => follow = true,
+                "-n" | "--lines" => {
+                    query.limit = Some(
+                        iter.next()
+                            .and_then(|n| n.parse().ok())
+                            .ok_or("-n expects a number")?,
+                    );
+                }
+                "--grep" => {
+                    query.search = Some(iter.next().ok_or("--grep expects a pattern")?.to_string());
+                    query.regex = true;
+                }
+                _ => positional.push(arg),
+            }
+        }
+        let id = self.resolve(process_arg(&positional)?)?;
+
+        let page: LogPage = self.call("query_logs", json!({ "process_id": id, "query": query }))?;
+        for entry in &page.entries {
+            self.print_log(&serde_json::to_value(entry).map_err(|e| e.to_string())?);
+        }
+
+        if follow {
+            let pattern = match &query.search {
+                Some(search) => Some(regex::Regex::new(search).map_err(|e| e.to_string())?),
+                None => None,
+            };
+            self.follow(&id, pattern)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn follow(&self, id: &str, pattern: Option<regex::Regex>) -> Result<(), String> {
+        let log_event = format!("process:log:{}", id);
+        self.client.subscribe(|event, payload| {
+            let message = payload.get("message").and_then(Value::as_str).unwrap_or_default();
+            if event == log_event && pattern.as_ref().is_none_or(|p| p.is_match(message)) {
+                self.print_log(&payload);
+            }
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn follow(&self, _id: &str, _pattern: Option<regex::Regex>) -> Result<(), String> {
+        Err("following logs requires the daemon".to_string())
+    }
+
+    fn export(&self, args: &[&str]) -> Result<(), String> {
+        let mut output = None;
+        let mut format = LogExportFormat::Text;
+        let mut query = LogQuery::default();
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(&arg) = iter.next() {
+            let mut value = || iter.next().copied().ok_or(format!("{} expects a value", arg));
+            match arg {
+                "-o" | "--output" => output = Some(value()?),
+                "--format" => {
+                    format = match value()? {
+                        "text" => LogExportFormat::Text,
+                        "jsonl" => LogExportFormat::Jsonl,
+                        "csv" => LogExportFormat::Csv,
+                        other => return Err(format!("unknown format '{}'", other)),
+                    }
+                }
+                "--since" => query.since = Some(value()?.to_string()),
+                "--until" => query.until = Some(value()?.to_string()),
+                "--stream" => query.streams.push(value()?.to_string()),
+                _ => positional.push(arg),
+            }
+        }
+        let reference = process_arg(&positional)?;
+        let id = self.resolve(reference)?;
+        let extension = match format {
+            LogExportFormat::Text => "log",
+            LogExportFormat::Jsonl => "jsonl",
+            LogExportFormat::Csv => "csv",
+        };
+        let path = absolute_path(&output.map_or_else(|| format!("{}.{}", reference, extension), str::to_string))?;
+
+        let count: usize = self.call(
+            "export_logs",
+            json!({ "process_id": id, "path": path, "format": format, "query": query }),
+        )?;
+        if self.json {
+            println!("{}", json!({ "id": id, "path": path, "entries": count }));
+        } else {
+            println!("Exported {} entries to {}", count, path);
+        }
+        Ok(())
+    }
+
+    fn bundle(&self, args: &[&str]) -> Result<(), String> {
+        let mut output = None;
+        let mut since = None;
+        let mut process_ids = Vec::new();
+        let mut iter = args.iter();
+        while let Some(&arg) = iter.next() {
+            let mut value = || iter.next().copied().ok_or(format!("{} expects a value", arg));
+            match arg {
+                "-o" | "--output" => output = Some(value()?.to_string()),
+                "--since" => since = Some(value()?.to_string()),
+                _ => process_ids.push(self.resolve(arg)?),
+            }
+        }
+        let path = absolute_path(&output.unwrap_or_else(|| {
+            format!("pm-bundle-{}.zip", chrono::Local::now().format("%Y%m%d_%H%M%S"))
+        }))?;
+
+        self.call::<()>(
+            "create_support_bundle",
+            json!({ "path": path, "process_ids": process_ids, "since": since }),
+        )?;
+        if self.json {
+            println!("{}", json!({ "path": path }));
+        } else {
+            println!("Wrote {}", path);
+        }
+        Ok(())
+    }
+
+    fn add(&self, args: &[&str]) -> Result<(), String> {
+        let split = args
+            .iter()
+            .position(|a| *a == "--")
+            .ok_or("usage: pm add [--name N] [--cwd DIR] [--env K=V]... -- <cmd> [args]")?;
+        let (options, command) = (&args[..split], &args[split + 1..]);
+        let Some((&exe, exe_args)) = command.split_first() else {
+            return Err("missing command after --".to_string());
+        };
+
+        let mut name = None;
+        let mut working_dir = None;
+        let mut env = HashMap::new();
+        let mut iter = options.iter();
+        while let Some(&option) = iter.next() {
+            let mut value = || iter.next().copied().ok_or(format!("{} expects a value", option));
+            match option {
+                "--name" => name = Some(value()?.to_string()),
+                "--cwd" => working_dir = Some(absolute_path(value()?)?),
+                "--env" => {
+                    let (key, val) = value()?
+                        .split_once('=')
+                        .ok_or("--env expects KEY=VALUE")?;
+                    env.insert(key.to_string(), val.to_string());
+                }
+                _ => return Err(format!("unknown option '{}'", option)),
+            }
+        }
+        // Relative commands and arguments resolve where `pm` was run.
+        let working_dir = match working_dir {
+            Some(dir) => dir,
+            None => std::env::current_dir().map_err(|e| e.to_string())?.to_string_lossy().to_string(),
+        };
+        let name = name.unwrap_or_else(|| {
+            std::path::Path::new(exe)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| exe.to_string())
+        });
+
+        let id: String = self.call(
+            "add_process",
+            json!({
+                "name": name,
+                "command": exe,
+                "args": exe_args,
+                "working_dir": working_dir,
+                "env": env,
+            }),
+        )?;
+        if self.json {
+            println!("{}", json!({ "id": id, "name": name }));
+        } else {
+            println!("Added {} ({})", name, id);
+        }
+        Ok(())
+    }
+
+    fn import(&self, args: &[&str]) -> Result<(), String> {
+        let mut format = None;
+        let mut run_js = false;
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(&arg) = iter.next() {
+            match arg {
+                "--format" => {
+                    format = Some(match iter.next().copied().ok_or("--format expects a value")? {
+                        "compose" => ImportFormat::Compose,
+                        "procfile" => ImportFormat::Procfile,
+                        "pm2" => ImportFormat::Pm2,
+                        other => return Err(format!("unknown format '{}'", other)),
+                    })
+                }
+                "--run-js" => run_js = true,
+                _ => positional.push(arg),
+            }
+        }
+        let [file] = positional.as_slice() else {
+            return Err("usage: pm import <file> [--format compose|procfile|pm2] [--run-js]".to_string());
+        };
+        let path = absolute_path(file)?;
+
+        let report: ImportReport = self.call("import_processes", json!({ "path": path, "format": format, "run_js": run_js }))?;
+        if self.json {
+            println!("{}", serde_json::to_string(&report).map_err(|e| e.to_string())?);
+            return Ok(());
+        }
+        let processes: Vec<ProcessState> = self.call("get_processes", json!({}))?;
+        for id in &report.process_ids {
+            let name = processes.iter().find(|p| &p.id == id).map_or(id.as_str(), |p| p.name.as_str());
+            println!("Added {} ({})", name, id);
+        }
+        for warning in &report.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        Ok(())
+    }
+
+    fn save(&self, args: &[&str]) -> Result<(), String> {
+        let force = match args {
+            [] => false,
+            ["--force"] => true,
+            _ => return Err("usage: pm save [--force]".to_string()),
+        };
+        self.call::<()>("save_config", json!({ "force": force }))?;
+        if self.json {
+            println!("{}", json!({ "saved": true }));
+        } else {
+            println!("Saved");
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let issues: Vec<ConfigIssue> = self.call("check_config", json!({}))?;
+        if self.json {
+            println!("{}", serde_json::to_string(&issues).map_err(|e| e.to_string())?);
+        } else if issues.is_empty() {
+            println!("No problems found");
+        } else {
+            for issue in &issues {
+                println!("{}", issue);
+            }
+        }
+        let errors = issues.iter().filter(|i| i.severity == IssueSeverity::Error).count();
+        if errors > 0 {
+            return Err(format!("{} process(es) can't be loaded", errors));
+        }
+        Ok(())
+    }
+
+    fn backups(&self) -> Result<(), String> {
+        let backups: Vec<ConfigBackup> = self.call("list_config_backups", json!({}))?;
+        if self.json {
+            println!("{}", serde_json::to_string(&backups).map_err(|e| e.to_string())?);
+            return Ok(());
+        }
+        if backups.is_empty() {
+            println!("No backups");
+        }
+        for backup in &backups {
+            let created = chrono::Local
+                .timestamp_millis_opt(backup.created_ms as i64)
+                .single()
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            println!("{:<40} {}  {:>8} B", backup.name, created, backup.size);
+        }
+        Ok(())
+    }
+
+    fn restore(&self, args: &[&str]) -> Result<(), String> {
+        let [name] = args else {
+            return Err("usage: pm restore <backup>".to_string());
+        };
+        let processes: Vec<ProcessState> = self.call("restore_config_backup", json!({ "name": name }))?;
+        if self.json {
+            println!("{}", serde_json::to_string(&processes).map_err(|e| e.to_string())?);
+        } else {
+            println!("Restored {} ({} processes)", name, processes.len());
+        }
+        Ok(())
+    }
+
+    /// An open workspace, by name or by its directory or project file.
+    fn resolve_workspace(&self, reference: Option<&str>) -> Result<String, String> {
+        let workspaces: Vec<WorkspaceInfo> = self.call("list_workspaces", json!({}))?;
+        if let Some(ws) = reference.and_then(|r| workspaces.iter().find(|ws| ws.name == r)) {
+            return Ok(ws.name.clone());
+        }
+        let path = workspace::locate(std::path::Path::new(&absolute_path(reference.unwrap_or("."))?));
+        workspaces
+            .into_iter()
+            .find(|ws| std::path::Path::new(&ws.path) == path)
+            .map(|ws| ws.name)
+            .ok_or_else(|| format!("no open workspace for '{}'", reference.unwrap_or(".")))
+    }
+
+    fn up(&self, args: &[&str]) -> Result<(), String> {
+        let reference = optional_arg(args)?;
+        let name = match self.resolve_workspace(reference) {
+            Ok(name) => name,
+            Err(_) => {
+                let path = absolute_path(reference.unwrap_or("."))?;
+                self.call::<WorkspaceInfo>("open_workspace", json!({ "path": path }))?.name
+            }
+        };
+        self.call::<()>("workspace_up", json!({ "name": name }))?;
+        if self.json {
+            println!("{}", json!({ "workspace": name }));
+        } else {
+            println!("Started {}", name);
+        }
+        Ok(())
+    }
+
+    /// Commands that take a workspace and return nothing.
+    fn workspace(&self, method: &str, verb: &str, args: &[&str]) -> Result<(), String> {
+        let name = self.resolve_workspace(optional_arg(args)?)?;
+        self.call::<()>(method, json!({ "name": name }))?;
+        if self.json {
+            println!("{}", json!({ "workspace": name }));
+        } else {
+            println!("{} {}", verb, name);
+        }
+        Ok(())
+    }
+
+    fn workspaces(&self) -> Result<(), String> {
+        let workspaces: Vec<WorkspaceInfo> = self.call("list_workspaces", json!({}))?;
+        if self.json {
+            println!("{}", serde_json::to_string(&workspaces).map_err(|e| e.to_string())?);
+            return Ok(());
+        }
+
+        let width = workspaces.iter().map(|ws| ws.name.len()).max().unwrap_or(0).max(4);
+        println!("{:<width$}  {:>9}  PATH", "NAME", "PROCESSES");
+        for ws in &workspaces {
+            println!("{:<width$}  {:>9}  {}", ws.name, ws.process_ids.len(), ws.path);
+        }
+        Ok(())
+    }
+}
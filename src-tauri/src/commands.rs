@@ -1,112 +1,49 @@
-use crate::config_handler::ConfigHandler;
-use crate::log_handler::LogHandler;
-use crate::monitoring::Monitoring;
-use crate::process_manager::ProcessManager;
-use crate::terminal::TerminalManager;
-use crate::types::{ProcessConfig, ProcessState};
+use crate::service::{self, AppState, Events, ProcessUpdate};
+use crate::types::{ConfigBackup, ConfigIssue, ImportFormat, ImportReport, LogCursor, LogEntry, LogExportFormat, LogPage, LogPolicy, LogQuery, LogTrigger, MetricsResolution, MetricsSample, ProcessState, ResourceLimits, RestartPolicy, WorkspaceInfo};
 use chrono::Local;
 use serde_json::json;
+use std::collections::HashMap;
 use std::io::BufRead;
-use std::process::{ChildStderr, ChildStdout};
 use std::sync::{Arc, Mutex};
-use sysinfo::System;
 use tauri::{Emitter, State, WebviewWindow};
 
-pub struct AppState {
-    pub manager: Arc<Mutex<ProcessManager>>,
-    pub log_handler: Arc<LogHandler>,
-    /// Persistent sysinfo System — keeps prior CPU snapshot so delta is accurate.
-    pub system: Arc<Mutex<System>>,
-    /// Integrated terminal sessions.
-    pub terminal: Arc<Mutex<TerminalManager>>,
+impl service::EventSink for WebviewWindow {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let _ = Emitter::emit(self, event, payload);
+    }
 }
 
-/// Spawn background threads that read stdout/stderr from a child process,
-/// write each line to the log file, and emit a real-time Tauri event.
-fn start_log_readers(
-    process_id: String,
-    stdout: ChildStdout,
-    stderr: ChildStderr,
-    log_handler: Arc<LogHandler>,
-    window: WebviewWindow,
-) {
-    // --- stdout ---
-    {
-        let lh = Arc::clone(&log_handler);
-        let id = process_id.clone();
-        let win = window.clone();
-        std::thread::spawn(move || {
-            let reader = std::io::BufReader::new(stdout);
-            for line in reader.lines() {
-                match line {
-                    Ok(msg) => {
-                        let ts = Local::now().format("%H:%M:%S%.3f").to_string();
-                        let _ = lh.write_log(&id, "stdout", &msg);
-                        let _ = win.emit(
-                            &format!("process:log:{}", id),
-                            json!({ "timestamp": ts, "level": "stdout", "message": msg }),
-                        );
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
-    }
-    // --- stderr ---
-    {
-        let lh = Arc::clone(&log_handler);
-        let id = process_id.clone();
-        let win = window.clone();
-        std::thread::spawn(move || {
-            let reader = std::io::BufReader::new(stderr);
-            for line in reader.lines() {
-                match line {
-                    Ok(msg) => {
-                        let ts = Local::now().format("%H:%M:%S%.3f").to_string();
-                        let _ = lh.write_log(&id, "stderr", &msg);
-                        let _ = win.emit(
-                            &format!("process:log:{}", id),
-                            json!({ "timestamp": ts, "level": "stderr", "message": msg }),
-                        );
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
-    }
+fn events(window: WebviewWindow) -> Events {
+    Arc::new(window)
 }
 
+// Each process command forwards to the background daemon when the GUI is
+// connected to one, and otherwise runs the operation in-process.
+
 #[tauri::command]
 pub async fn get_processes(state: State<'_, AppState>) -> Result<Vec<ProcessState>, String> {
-    let manager = state.manager.lock().map_err(|e| e.to_string())?;
-    Ok(manager.get_all_processes())
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("get_processes", json!({})).await;
+    }
+    service::get_processes(&state)
 }
 
 #[tauri::command]
 pub async fn start_process(
     process_id: String,
+    with_dependencies: Option<bool>,
     state: State<'_, AppState>,
     window: WebviewWindow,
 ) -> Result<u32, String> {
-    let (pid, stdout, stderr) = {
-        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-        let (pid, stdout, stderr) = manager.spawn_process(&process_id)?;
-        let _ = window.emit(
-            "process:status_changed",
-            json!({ "id": &process_id, "status": "Running", "pid": pid }),
-        );
-        (pid, stdout, stderr)
-    }; // lock released here
-
-    start_log_readers(
-        process_id,
-        stdout,
-        stderr,
-        Arc::clone(&state.log_handler),
-        window,
-    );
-
-    Ok(pid)
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "start_process",
+                json!({ "process_id": process_id, "with_dependencies": with_dependencies }),
+            )
+            .await;
+    }
+    service::start_process(&state, process_id, with_dependencies, events(window)).await
 }
 
 #[tauri::command]
@@ -115,19 +52,10 @@ pub async fn stop_process(
     state: State<'_, AppState>,
     window: WebviewWindow,
 ) -> Result<(), String> {
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-
-    manager.stop_process(&process_id)?;
-
-    let _ = window.emit(
-        "process:status_changed",
-        json!({
-            "id": process_id,
-            "status": "Stopped"
-        }),
-    );
-
-    Ok(())
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("stop_process", json!({ "process_id": process_id })).await;
+    }
+    service::stop_process(&state, process_id, events(window)).await
 }
 
 #[tauri::command]
@@ -136,25 +64,10 @@ pub async fn restart_process(
     state: State<'_, AppState>,
     window: WebviewWindow,
 ) -> Result<u32, String> {
-    let (pid, stdout, stderr) = {
-        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-        let (pid, stdout, stderr) = manager.restart_process(&process_id)?;
-        let _ = window.emit(
-            "process:status_changed",
-            json!({ "id": &process_id, "status": "Running", "pid": pid }),
-        );
-        (pid, stdout, stderr)
-    }; // lock released here
-
-    start_log_readers(
-        process_id,
-        stdout,
-        stderr,
-        Arc::clone(&state.log_handler),
-        window,
-    );
-
-    Ok(pid)
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("restart_process", json!({ "process_id": process_id })).await;
+    }
+    service::restart_process(&state, process_id, events(window)).await
 }
 
 #[tauri::command]
@@ -163,139 +76,291 @@ pub async fn add_process(
     command: String,
     args: Vec<String>,
     working_dir: Option<String>,
+    env: Option<HashMap<String, String>>,
+    env_file: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-    let id = manager.add_process(name, command, args, working_dir, false);
-    Ok(id)
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "add_process",
+                json!({
+                    "name": name,
+                    "command": command,
+                    "args": args,
+                    "working_dir": working_dir,
+                    "env": env,
+                    "env_file": env_file,
+                }),
+            )
+            .await;
+    }
+    service::add_process(&state, name, command, args, working_dir, env, env_file)
+}
+
+/// Add processes from a docker-compose file, Procfile or PM2 ecosystem
+/// file; the format is guessed from the file name when not given.
+#[tauri::command]
+pub async fn import_processes(
+    path: String,
+    format: Option<ImportFormat>,
+    run_js: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<ImportReport, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call("import_processes", json!({ "path": path, "format": format, "run_js": run_js }))
+            .await;
+    }
+    service::import_processes(&state, &path, format, run_js.unwrap_or(false))
 }
 
 #[tauri::command]
 pub async fn remove_process(
     process_id: String,
     state: State<'_, AppState>,
+    window: WebviewWindow,
 ) -> Result<(), String> {
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-
-    manager.stop_process(&process_id).ok();
-    manager.remove_process(&process_id);
-
-    Ok(())
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("remove_process", json!({ "process_id": process_id })).await;
+    }
+    service::remove_process(&state, process_id, events(window)).await
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn update_process(
     process_id: String,
     auto_restart: Option<bool>,
     auto_start: Option<bool>,
     working_dir: Option<String>,
+    env: Option<HashMap<String, String>>,
+    env_file: Option<String>,
+    clear_env: Option<bool>,
+    restart_policy: Option<RestartPolicy>,
+    depends_on: Option<Vec<String>>,
+    log_policy: Option<LogPolicy>,
+    output_encoding: Option<String>,
+    secret_env: Option<Vec<String>>,
+    triggers: Option<Vec<LogTrigger>>,
+    limits: Option<ResourceLimits>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+    let update = ProcessUpdate {
+        auto_restart,
+        auto_start,
+        working_dir,
+        env,
+        env_file,
+        clear_env,
+        restart_policy,
+        depends_on,
+        log_policy,
+        output_encoding,
+        secret_env,
+        triggers,
+        limits,
+    };
 
-    if let Some(process) = manager.get_process_mut(&process_id) {
-        if let Some(ar) = auto_restart {
-            process.auto_restart = ar;
-        }
-        if let Some(as_val) = auto_start {
-            process.auto_start = as_val;
-        }
-        // empty string means "clear working dir"
-        match working_dir {
-            Some(ref dir) if dir.is_empty() => process.working_dir = None,
-            Some(dir) => process.working_dir = Some(dir),
-            None => {}
-        }
+    if let Some(daemon) = &state.daemon {
+        let mut params = serde_json::to_value(&update).map_err(|e| e.to_string())?;
+        params["process_id"] = json!(process_id);
+        return daemon.call("update_process", params).await;
     }
-
-    Ok(())
+    service::update_process(&state, &process_id, update)
 }
 
 #[tauri::command]
 pub async fn get_metrics(process_id: String, state: State<'_, AppState>) -> Result<serde_json::Value, String> {
-    let pid = {
-        let manager = state.manager.lock().map_err(|e| e.to_string())?;
-        manager.get_process(&process_id).and_then(|p| p.pid)
-    };
-
-    if let Some(pid) = pid {
-        let mut system = state.system.lock().map_err(|e| e.to_string())?;
-        if let Ok(metrics) = Monitoring::get_process_metrics(pid, &mut system) {
-            return Ok(json!({
-                "cpu_percent": metrics.cpu_percent,
-                "memory_mb": metrics.memory_mb,
-                "memory_percent": metrics.memory_percent,
-            }));
-        }
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("get_metrics", json!({ "process_id": process_id })).await;
     }
+    service::get_metrics(&state, &process_id)
+}
 
-    Ok(json!({
-        "cpu_percent": 0.0,
-        "memory_mb": 0,
-        "memory_percent": 0.0,
-    }))
+/// Recorded metrics between `from` and `to` (ms since the epoch) at the
+/// given resolution, for charts.
+#[tauri::command]
+pub async fn get_metrics_history(
+    process_id: String,
+    from: Option<u64>,
+    to: Option<u64>,
+    resolution: Option<MetricsResolution>,
+    state: State<'_, AppState>,
+) -> Result<Vec<MetricsSample>, String> {
+    let resolution = resolution.unwrap_or_default();
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "get_metrics_history",
+                json!({ "process_id": process_id, "from": from, "to": to, "resolution": resolution }),
+            )
+            .await;
+    }
+    service::get_metrics_history(&state, &process_id, from, to, resolution)
 }
 
 #[tauri::command]
 pub async fn get_logs(
     process_id: String,
     state: State<'_, AppState>,
-) -> Result<Vec<serde_json::Value>, String> {
-    let logs = state
-        .log_handler
-        .read_logs(&process_id, Some(1000))
-        .map_err(|e| e.to_string())?;
+) -> Result<Vec<LogEntry>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("get_logs", json!({ "process_id": process_id })).await;
+    }
+    service::get_logs(&state, &process_id)
+}
 
-    let vec = logs
-        .iter()
-        .map(|log| {
-            json!({
-                "timestamp": log.timestamp,
-                "level": log.level,
-                "message": log.message
-            })
-        })
-        .collect();
+/// Page through a process's log by cursor and/or time range.
+#[tauri::command]
+pub async fn query_logs(
+    process_id: String,
+    query: LogQuery,
+    state: State<'_, AppState>,
+) -> Result<LogPage, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call("query_logs", json!({ "process_id": process_id, "query": query }))
+            .await;
+    }
+    service::query_logs(&state, &process_id, &query)
+}
 
-    Ok(vec)
+/// Newest log entries matching `search`, optionally limited to some streams
+/// or levels. Page further back by passing the returned `before` cursor.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn search_logs(
+    process_id: String,
+    search: String,
+    regex: Option<bool>,
+    ignore_case: Option<bool>,
+    streams: Option<Vec<String>>,
+    levels: Option<Vec<String>>,
+    before: Option<LogCursor>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<LogPage, String> {
+    let query = LogQuery {
+        search: Some(search),
+        regex: regex.unwrap_or(false),
+        ignore_case: ignore_case.unwrap_or(false),
+        streams: streams.unwrap_or_default(),
+        levels: levels.unwrap_or_default(),
+        before,
+        limit,
+        ..LogQuery::default()
+    };
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call("query_logs", json!({ "process_id": process_id, "query": query }))
+            .await;
+    }
+    service::query_logs(&state, &process_id, &query)
 }
 
+/// Export a process's log to `path` as text, JSONL or CSV. Cursors and
+/// `limit` in `query` are ignored; rotated segments are included.
 #[tauri::command]
-pub async fn clear_logs(
+pub async fn export_logs(
     process_id: String,
+    path: String,
+    format: Option<LogExportFormat>,
+    query: Option<LogQuery>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let format = format.unwrap_or_default();
+    let query = query.unwrap_or_default();
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "export_logs",
+                json!({ "process_id": process_id, "path": path, "format": format, "query": query }),
+            )
+            .await;
+    }
+    service::export_logs(&state, &process_id, &query, format, &path)
+}
+
+/// Write a support bundle zip for some processes, or all when none given.
+#[tauri::command]
+pub async fn create_support_bundle(
+    path: String,
+    process_ids: Option<Vec<String>>,
+    since: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    state
-        .log_handler
-        .clear_logs(&process_id)
-        .map_err(|e| e.to_string())
+    let process_ids = process_ids.unwrap_or_default();
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "create_support_bundle",
+                json!({ "path": path, "process_ids": process_ids, "since": since }),
+            )
+            .await;
+    }
+    service::create_support_bundle(&state, &process_ids, since, &path)
 }
 
 #[tauri::command]
-pub async fn save_config(state: State<'_, AppState>) -> Result<(), String> {
-    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+pub async fn clear_logs(
+    process_id: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("clear_logs", json!({ "process_id": process_id })).await;
+    }
+    service::clear_logs(&state, &process_id)
+}
 
-    let configs: Vec<ProcessConfig> = manager
-        .processes
-        .values()
-        .map(|p| p.to_config())
-        .collect();
-    drop(manager);
+#[tauri::command]
+pub async fn save_config(
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("save_config", json!({ "force": force })).await;
+    }
+    service::save_config(&state, force.unwrap_or(false))
+}
 
-    ConfigHandler::save_configs(&configs).map_err(|e| e.to_string())
+#[tauri::command]
+pub async fn load_config(
+    state: State<'_, AppState>,
+    window: WebviewWindow,
+) -> Result<Vec<ProcessState>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("load_config", json!({})).await;
+    }
+    service::load_config(&state, events(window)).await
 }
 
 #[tauri::command]
-pub async fn load_config(state: State<'_, AppState>) -> Result<Vec<ProcessState>, String> {
-    let configs = ConfigHandler::load_configs().map_err(|e| e.to_string())?;
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+pub async fn check_config(state: State<'_, AppState>) -> Result<Vec<ConfigIssue>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("check_config", json!({})).await;
+    }
+    service::check_config()
+}
 
-    for config in configs {
-        let instance = crate::process_manager::ProcessInstance::from_config(&config);
-        manager.processes.insert(instance.id.clone(), instance);
+#[tauri::command]
+pub async fn list_config_backups(state: State<'_, AppState>) -> Result<Vec<ConfigBackup>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("list_config_backups", json!({})).await;
     }
+    service::list_config_backups()
+}
 
-    Ok(manager.get_all_processes())
+#[tauri::command]
+pub async fn restore_config_backup(
+    name: String,
+    state: State<'_, AppState>,
+    window: WebviewWindow,
+) -> Result<Vec<ProcessState>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("restore_config_backup", json!({ "name": name })).await;
+    }
+    service::restore_config_backup(&state, &name, events(window)).await
 }
 
 #[tauri::command]
@@ -334,52 +399,75 @@ pub async fn start_all(
     state: State<'_, AppState>,
     window: WebviewWindow,
 ) -> Result<(), String> {
-    let spawned = {
-        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-        let process_ids: Vec<String> = manager.processes.keys().cloned().collect();
-        let mut spawned = Vec::new();
-        for process_id in process_ids {
-            if let Ok((pid, stdout, stderr)) = manager.spawn_process(&process_id) {
-                let _ = window.emit(
-                    "process:status_changed",
-                    json!({ "id": &process_id, "status": "Running", "pid": pid }),
-                );
-                spawned.push((process_id, stdout, stderr));
-            }
-        }
-        spawned
-    }; // lock released here
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("start_all", json!({})).await;
+    }
+    service::start_all(&state, events(window)).await
+}
 
-    for (process_id, stdout, stderr) in spawned {
-        start_log_readers(
-            process_id,
-            stdout,
-            stderr,
-            Arc::clone(&state.log_handler),
-            window.clone(),
-        );
+#[tauri::command]
+pub async fn stop_all(state: State<'_, AppState>, window: WebviewWindow) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("stop_all", json!({})).await;
     }
+    service::stop_all(&state, events(window)).await
+}
 
-    Ok(())
+// ═══════════════════════════════════════════════════════════════
+// Workspace commands
+// ═══════════════════════════════════════════════════════════════
+
+/// Open a project file, or the `pm.toml` in a directory, as a workspace.
+#[tauri::command]
+pub async fn open_workspace(path: String, state: State<'_, AppState>) -> Result<WorkspaceInfo, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("open_workspace", json!({ "path": path })).await;
+    }
+    service::open_workspace(&state, &path)
 }
 
 #[tauri::command]
-pub async fn stop_all(state: State<'_, AppState>, window: WebviewWindow) -> Result<(), String> {
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+pub async fn close_workspace(
+    name: String,
+    state: State<'_, AppState>,
+    window: WebviewWindow,
+) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("close_workspace", json!({ "name": name })).await;
+    }
+    service::close_workspace(&state, &name, events(window)).await
+}
 
-    let process_ids: Vec<String> = manager.processes.keys().cloned().collect();
-    for process_id in process_ids {
-        manager.stop_process(&process_id).ok();
-        let _ = window.emit(
-            "process:status_changed",
-            json!({
-                "id": process_id,
-                "status": "Stopped"
-            }),
-        );
+#[tauri::command]
+pub async fn list_workspaces(state: State<'_, AppState>) -> Result<Vec<WorkspaceInfo>, String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("list_workspaces", json!({})).await;
     }
+    service::list_workspaces(&state)
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn workspace_up(
+    name: String,
+    state: State<'_, AppState>,
+    window: WebviewWindow,
+) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("workspace_up", json!({ "name": name })).await;
+    }
+    service::workspace_up(&state, &name, events(window)).await
+}
+
+#[tauri::command]
+pub async fn workspace_down(
+    name: String,
+    state: State<'_, AppState>,
+    window: WebviewWindow,
+) -> Result<(), String> {
+    if let Some(daemon) = &state.daemon {
+        return daemon.call("workspace_down", json!({ "name": name })).await;
+    }
+    service::workspace_down(&state, &name, events(window)).await
 }
 
 // ═══════════════════════════════════════════════════════════════
@@ -546,7 +634,13 @@ pub async fn terminal_add_process(
         vec![]
     };
 
-    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
-    let id = manager.add_process(name, exe, args, working_dir, false);
-    Ok(id)
+    if let Some(daemon) = &state.daemon {
+        return daemon
+            .call(
+                "add_process",
+                json!({ "name": name, "command": exe, "args": args, "working_dir": working_dir }),
+            )
+            .await;
+    }
+    service::add_process(&state, name, exe, args, working_dir, None, None)
 }
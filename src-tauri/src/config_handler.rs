@@ -1,6 +1,65 @@
-use crate::types::ProcessConfig;
-use std::fs;
-use std::path::PathBuf;
+use crate::config_schema::{self, ConfigDocument, LoadedConfig, CONFIG_VERSION};
+use crate::types::{AppSettings, ConfigBackup, ProcessConfig};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CONFIG_FILE: &str = "processes.json";
+
+/// Returned when saving would overwrite another instance's edits.
+pub const CONFLICT: &str = "processes.json was changed by another instance since it was last loaded";
+
+/// What `processes.json` held when this instance last read or wrote it,
+/// to notice edits made by someone else in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Revision(Option<u64>);
+
+impl Revision {
+    fn of(contents: Option<&str>) -> Self {
+        Revision(contents.map(|contents| {
+            let mut hasher = DefaultHasher::new();
+            contents.hash(&mut hasher);
+            hasher.finish()
+        }))
+    }
+}
+
+fn read_optional(path: &Path) -> std::io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace `path` so readers see either the old or the new contents, even
+/// if we crash or the disk fills up mid-write: write a temp file beside
+/// it, fsync, rename it over the original, then fsync the directory.
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    let written = File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with("processes-") && name.ends_with(".json") && !name.contains(['/', '\\'])
+}
 
 pub struct ConfigHandler;
 
@@ -29,25 +88,186 @@ impl ConfigHandler {
         Ok(logs_dir)
     }
 
-    pub fn load_configs() -> Result<Vec<ProcessConfig>, Box<dyn std::error::Error>> {
-        let config_dir = Self::get_config_dir()?;
-        let config_file = config_dir.join("processes.json");
+    pub fn get_config_file() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(Self::get_config_dir()?.join(CONFIG_FILE))
+    }
+
+    pub fn get_backups_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let backups_dir = Self::get_config_dir()?.join("backups");
+        fs::create_dir_all(&backups_dir)?;
+        Ok(backups_dir)
+    }
+
+    pub fn get_metrics_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let metrics_dir = Self::get_config_dir()?.join("metrics");
+        fs::create_dir_all(&metrics_dir)?;
+        Ok(metrics_dir)
+    }
+
+    /// App settings from `processes.json`, or the `settings.json` that
+    /// held them before the versioned document.
+    pub fn load_settings() -> Result<AppSettings, Box<dyn std::error::Error>> {
+        Ok(Self::load_configs()?.0.settings)
+    }
+
+    /// Control socket of the background daemon.
+    pub fn get_socket_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(Self::get_config_dir()?.join("pm.sock"))
+    }
+
+    /// Load `processes.json` of any schema version; problems with single
+    /// processes are returned as issues rather than failing the load.
+    pub fn load_configs() -> Result<(LoadedConfig, Revision), Box<dyn std::error::Error>> {
+        let legacy_settings = Self::load_legacy_settings()?;
+        let Some(contents) = read_optional(&Self::get_config_file()?)? else {
+            let loaded = LoadedConfig {
+                processes: Vec::new(),
+                settings: legacy_settings
+                    .and_then(|settings| serde_json::from_value(settings).ok())
+                    .unwrap_or_default(),
+                issues: Vec::new(),
+            };
+            return Ok((loaded, Revision::of(None)));
+        };
+        let loaded = config_schema::parse(&contents, legacy_settings)?;
+        Ok((loaded, Revision::of(Some(&contents))))
+    }
 
-        if !config_file.exists() {
+    /// `settings.json`, from before settings moved into `processes.json`.
+    fn load_legacy_settings() -> Result<Option<Value>, Box<dyn std::error::Error>> {
+        let path = Self::get_config_dir()?.join("settings.json");
+        Ok(fs::read_to_string(path).ok().and_then(|contents| {
+            serde_json::from_str(&contents)
+                .map_err(|e| eprintln!("Ignoring invalid settings.json: {}", e))
+                .ok()
+        }))
+    }
+
+    /// Project files of the workspaces to reopen on launch.
+    pub fn load_workspaces() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let workspaces_file = Self::get_config_dir()?.join("workspaces.json");
+        if !workspaces_file.exists() {
             return Ok(Vec::new());
         }
+        let contents = fs::read_to_string(&workspaces_file)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
 
-        let contents = fs::read_to_string(&config_file)?;
-        let configs: Vec<ProcessConfig> = serde_json::from_str(&contents)?;
-        Ok(configs)
+    pub fn save_workspaces(paths: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+        let workspaces_file = Self::get_config_dir()?.join("workspaces.json");
+        write_atomic(&workspaces_file, serde_json::to_string_pretty(paths)?.as_bytes())?;
+        Ok(())
     }
 
-    pub fn save_configs(configs: &[ProcessConfig]) -> Result<(), Box<dyn std::error::Error>> {
-        let config_dir = Self::get_config_dir()?;
-        let config_file = config_dir.join("processes.json");
+    /// Saved copies of `processes.json`, newest first.
+    pub fn list_backups() -> Result<Vec<ConfigBackup>, Box<dyn std::error::Error>> {
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(Self::get_backups_dir()?)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_backup_name(&name) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let created_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64);
+            backups.push(ConfigBackup {
+                name,
+                created_ms,
+                size: metadata.len(),
+            });
+        }
+        // Names embed the timestamp, so they sort chronologically.
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
+
+    /// Keep a copy of the file being replaced, then prune to the newest `keep`.
+    fn back_up(contents: &str, keep: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if keep == 0 {
+            return Ok(());
+        }
+        let backups_dir = Self::get_backups_dir()?;
+        let name = format!("processes-{}.json", chrono::Local::now().format("%Y%m%d-%H%M%S-%3f"));
+        write_atomic(&backups_dir.join(name), contents.as_bytes())?;
 
-        let contents = serde_json::to_string_pretty(configs)?;
-        fs::write(&config_file, contents)?;
+        for backup in Self::list_backups()?.into_iter().skip(keep) {
+            fs::remove_file(backups_dir.join(backup.name))?;
+        }
         Ok(())
     }
+
+    /// Atomically replace `processes.json`, backing up what it held. With
+    /// `expected`, refuses to if the file changed since that revision.
+    fn replace_config(
+        contents: &str,
+        expected: Option<Revision>,
+        keep_backups: usize,
+    ) -> Result<Revision, Box<dyn std::error::Error>> {
+        let config_file = Self::get_config_file()?;
+        let current = read_optional(&config_file)?;
+        if expected.is_some_and(|expected| expected != Revision::of(current.as_deref())) {
+            return Err(CONFLICT.into());
+        }
+
+        if let Some(current) = current.filter(|current| current != contents) {
+            Self::back_up(&current, keep_backups)?;
+        }
+        write_atomic(&config_file, contents.as_bytes())?;
+        Ok(Revision::of(Some(contents)))
+    }
+
+    /// Make a backup the current `processes.json`; the file it replaces is
+    /// backed up in turn.
+    pub fn restore_backup(
+        name: &str,
+        expected: Option<Revision>,
+    ) -> Result<(LoadedConfig, Revision), Box<dyn std::error::Error>> {
+        let path = Self::get_backups_dir()?.join(name);
+        if !is_backup_name(name) || !path.is_file() {
+            return Err(format!("Unknown backup '{}'", name).into());
+        }
+        let contents = fs::read_to_string(&path)?;
+        let loaded = config_schema::parse(&contents, None)?;
+        let revision = Self::replace_config(&contents, expected, loaded.settings.config_backups)?;
+        Ok((loaded, revision))
+    }
+
+    /// Write the processes as the current document version, keeping the
+    /// settings and the entries that failed to load from the file on disk
+    /// as they are; nothing is validated on the way. See [`Self::replace_config`] for `expected`.
+    pub fn save_configs(
+        configs: &[ProcessConfig],
+        expected: Option<Revision>,
+    ) -> Result<Revision, Box<dyn std::error::Error>> {
+        let legacy_settings = Self::load_legacy_settings()?;
+        let (settings, rejected) = match read_optional(&Self::get_config_file()?)? {
+            None => (legacy_settings.unwrap_or_else(|| json!({})), Vec::new()),
+            Some(contents) => match serde_json::from_str(&contents) {
+                Ok(doc) => config_schema::preserved(doc, legacy_settings)?,
+                // Nothing to keep from a file that isn't JSON; it is backed up.
+                Err(_) => (json!({}), Vec::new()),
+            },
+        };
+
+        let mut processes = configs
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        processes.extend(rejected);
+        let keep_backups = settings
+            .get("config_backups")
+            .and_then(Value::as_u64)
+            .map_or(AppSettings::default().config_backups, |n| n as usize);
+        let document = ConfigDocument {
+            version: CONFIG_VERSION,
+            processes,
+            settings: &settings,
+        };
+        let contents = serde_json::to_string_pretty(&document)?;
+        Self::replace_config(&contents, expected, keep_backups)
+    }
 }
@@ -0,0 +1,416 @@
+//! The `processes.json` document: its schema version, migrations from
+//! older files, and per-process validation so one bad entry is reported
+//! and skipped instead of failing the whole load. Skipped entries are kept
+//! as they are and written back on save, so fixing the file brings them back.
+
+use crate::types::{AppSettings, ConfigIssue, IssueSeverity, ProcessConfig};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Version written by this build.
+pub const CONFIG_VERSION: u64 = 1;
+
+#[derive(Serialize)]
+pub struct ConfigDocument<'a> {
+    pub version: u64,
+    pub processes: Vec<Value>,
+    /// Kept as stored, so settings this build can't read aren't reset.
+    pub settings: &'a Value,
+}
+
+pub struct LoadedConfig {
+    pub processes: Vec<ProcessConfig>,
+    pub settings: AppSettings,
+    pub issues: Vec<ConfigIssue>,
+}
+
+fn issue(severity: IssueSeverity, process: Option<&str>, field: Option<&str>, message: String) -> ConfigIssue {
+    ConfigIssue {
+        severity,
+        process: process.map(str::to_string),
+        field: field.map(str::to_string),
+        message,
+    }
+}
+
+/// Upgrade a document to [`CONFIG_VERSION`] one version at a time.
+/// `legacy_settings` is the old `settings.json`, if there is one.
+fn migrate(mut doc: Value, mut legacy_settings: Option<Value>) -> Result<Value, String> {
+    let mut version = match &doc {
+        Value::Array(_) => 0,
+        _ => doc
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or("processes.json has no `version`")?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "processes.json is version {}, newer than this build understands ({}); update the app",
+            version, CONFIG_VERSION
+        ));
+    }
+
+    while version < CONFIG_VERSION {
+        doc = match version {
+            // v0 was a bare array of processes, with settings in settings.json.
+            0 => json!({
+                "processes": doc,
+                "settings": legacy_settings.take().unwrap_or_else(|| json!({})),
+            }),
+            _ => unreachable!("no migration from version {}", version),
+        };
+        version += 1;
+        doc["version"] = version.into();
+    }
+    Ok(doc)
+}
+
+/// Parse `processes.json` of any version. Only a file that isn't JSON at
+/// all, or is from a newer version, fails as a whole; bad processes are
+/// reported as errors and left out.
+pub fn parse(contents: &str, legacy_settings: Option<Value>) -> Result<LoadedConfig, String> {
+    let doc: Value = serde_json::from_str(contents).map_err(|e| format!("processes.json is not valid JSON: {}", e))?;
+    let mut doc = migrate(doc, legacy_settings)?;
+    let mut issues = Vec::new();
+
+    let settings = match doc.get_mut("settings").map(Value::take) {
+        None | Some(Value::Null) => AppSettings::default(),
+        Some(value) => serde_path_to_error::deserialize(value).unwrap_or_else(|e| {
+            let field = e.path().to_string();
+            issues.push(issue(IssueSeverity::Error, None, Some(&format!("settings.{}", field)), e.into_inner().to_string()));
+            AppSettings::default()
+        }),
+    };
+
+    let entries = match doc.get_mut("processes").map(Value::take) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(entries)) => entries,
+        Some(_) => return Err("`processes` in processes.json must be a list".to_string()),
+    };
+
+    let (processes, _) = read_processes(entries, &mut issues);
+    validate(&processes, &mut issues);
+    Ok(LoadedConfig { processes, settings, issues })
+}
+
+/// Split stored entries into the processes that load and the raw entries
+/// that don't: ones that fail to parse, have no command or reuse an id.
+fn read_processes(entries: Vec<Value>, issues: &mut Vec<ConfigIssue>) -> (Vec<ProcessConfig>, Vec<Value>) {
+    let mut processes = Vec::new();
+    let mut rejected = Vec::new();
+    let mut ids = HashSet::new();
+    for (i, entry) in entries.into_iter().enumerate() {
+        let label = entry
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("#{}", i + 1));
+        let keys: Vec<String> = entry.as_object().map(|o| o.keys().cloned().collect()).unwrap_or_default();
+
+        let config: ProcessConfig = match serde_path_to_error::deserialize(entry.clone()) {
+            Ok(config) => config,
+            Err(e) => {
+                let field = e.path().to_string();
+                let field = (field != ".").then_some(field);
+                issues.push(issue(IssueSeverity::Error, Some(&label), field.as_deref(), e.into_inner().to_string()));
+                rejected.push(entry);
+                continue;
+            }
+        };
+        if config.command.trim().is_empty() {
+            issues.push(issue(IssueSeverity::Error, Some(&config.name), Some("command"), "command is empty".to_string()));
+            rejected.push(entry);
+            continue;
+        }
+        if !ids.insert(config.id.clone()) {
+            issues.push(issue(
+                IssueSeverity::Error,
+                Some(&config.name),
+                Some("id"),
+                format!("id {} is already used by another process", config.id),
+            ));
+            rejected.push(entry);
+            continue;
+        }
+
+        // Serde ignores unknown fields; a misspelt one would vanish silently.
+        let known = serde_json::to_value(&config).unwrap_or_default();
+        for key in keys.iter().filter(|k| known.get(k.as_str()).is_none()) {
+            issues.push(issue(IssueSeverity::Warning, Some(&label), Some(key), "unknown field, ignored".to_string()));
+        }
+        processes.push(config);
+    }
+    (processes, rejected)
+}
+
+/// What saving carries over from the stored document `doc`: its
+/// `settings`, verbatim, and the process entries that don't load. Unlike
+/// [`parse`] it skips the filesystem checks.
+pub fn preserved(doc: Value, legacy_settings: Option<Value>) -> Result<(Value, Vec<Value>), String> {
+    let mut doc = migrate(doc, legacy_settings)?;
+    let settings = match doc.get_mut("settings").map(Value::take) {
+        None | Some(Value::Null) => json!({}),
+        Some(settings) => settings,
+    };
+    let entries = match doc.get_mut("processes").map(Value::take) {
+        Some(Value::Array(entries)) => entries,
+        _ => Vec::new(),
+    };
+    let (_, rejected) = read_processes(entries, &mut Vec::new());
+    Ok((settings, rejected))
+}
+
+/// Whether `command` would be found: an existing file when it is a path
+/// (relative ones against the working directory), otherwise a program on
+/// the PATH the process would get.
+fn program_exists(config: &ProcessConfig) -> bool {
+    let command = Path::new(&config.command);
+    if command.is_absolute() || command.components().count() > 1 {
+        return match &config.working_dir {
+            Some(dir) if command.is_relative() => Path::new(dir).join(command).is_file(),
+            _ => command.is_file(),
+        };
+    }
+
+    let path = match config.env.as_ref().and_then(|env| env.get("PATH")) {
+        Some(path) => path.into(),
+        None if config.clear_env => return false,
+        None => match std::env::var_os("PATH") {
+            Some(path) => path,
+            None => return false,
+        },
+    };
+    #[cfg(target_os = "windows")]
+    let extensions: Vec<String> = std::iter::once(String::new())
+        .chain(
+            std::env::var("PATHEXT")
+                .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+                .split(';')
+                .map(str::to_string),
+        )
+        .collect();
+    #[cfg(not(target_os = "windows"))]
+    let extensions = [String::new()];
+
+    std::env::split_paths(&path).any(|dir| {
+        extensions
+            .iter()
+            .any(|ext| dir.join(format!("{}{}", config.command, ext)).is_file())
+    })
+}
+
+/// Cross-process and filesystem checks; they only report. A dependency
+/// that doesn't resolve to exactly one process is kept in the file but
+/// not waited for.
+fn validate(processes: &[ProcessConfig], issues: &mut Vec<ConfigIssue>) {
+    let ids: HashSet<&str> = processes.iter().map(|c| c.id.as_str()).collect();
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for config in processes.iter() {
+        *name_counts.entry(config.name.clone()).or_default() += 1;
+    }
+    let mut reported = HashSet::new();
+    for config in processes.iter() {
+        if name_counts[&config.name] > 1 && reported.insert(config.name.clone()) {
+            issues.push(issue(
+                IssueSeverity::Warning,
+                Some(&config.name),
+                Some("name"),
+                "name is used by more than one process; refer to them by id".to_string(),
+            ));
+        }
+    }
+
+    for config in processes {
+        let name = config.name.clone();
+        if !program_exists(config) {
+            issues.push(issue(
+                IssueSeverity::Warning,
+                Some(&name),
+                Some("command"),
+                format!("'{}' was not found", config.command),
+            ));
+        }
+        if let Some(dir) = config.working_dir.as_deref().filter(|d| !Path::new(d).is_dir()) {
+            issues.push(issue(
+                IssueSeverity::Warning,
+                Some(&name),
+                Some("working_dir"),
+                format!("directory {} does not exist", dir),
+            ));
+        }
+        if let Some(env_file) = &config.env_file {
+            let path = match &config.working_dir {
+                Some(dir) => Path::new(dir).join(env_file),
+                None => Path::new(env_file).to_path_buf(),
+            };
+            if !path.is_file() {
+                issues.push(issue(
+                    IssueSeverity::Warning,
+                    Some(&name),
+                    Some("env_file"),
+                    format!("{} does not exist", path.display()),
+                ));
+            }
+        }
+
+        for dep in &config.depends_on {
+            if ids.contains(dep.as_str()) || name_counts.get(dep) == Some(&1) {
+                continue;
+            }
+            let problem = if name_counts.contains_key(dep) { "ambiguous" } else { "unknown" };
+            issues.push(issue(
+                IssueSeverity::Warning,
+                Some(&name),
+                Some("depends_on"),
+                format!("{} dependency '{}' is ignored", problem, dep),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A command that exists wherever the tests run.
+    fn command() -> String {
+        std::env::current_exe().unwrap().to_string_lossy().to_string()
+    }
+
+    fn messages(issues: &[ConfigIssue], severity: IssueSeverity) -> Vec<String> {
+        issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .map(|i| format!("{}/{}: {}", i.process.as_deref().unwrap_or("-"), i.field.as_deref().unwrap_or("-"), i.message))
+            .collect()
+    }
+
+    #[test]
+    fn migrates_bare_array_with_legacy_settings() {
+        let doc = migrate(json!([{ "name": "a" }]), Some(json!({ "config_backups": 3 }))).unwrap();
+        assert_eq!(
+            doc,
+            json!({ "version": CONFIG_VERSION, "processes": [{ "name": "a" }], "settings": { "config_backups": 3 } })
+        );
+        let doc = migrate(json!([]), None).unwrap();
+        assert_eq!(doc["settings"], json!({}));
+
+        let current = json!({ "version": CONFIG_VERSION, "processes": [], "settings": {} });
+        assert_eq!(migrate(current.clone(), Some(json!({ "ignored": true }))).unwrap(), current);
+    }
+
+    #[test]
+    fn migrate_rejects_unversioned_and_newer_documents() {
+        assert!(migrate(json!({ "processes": [] }), None).unwrap_err().contains("no `version`"));
+        let newer = migrate(json!({ "version": CONFIG_VERSION + 1 }), None).unwrap_err();
+        assert!(newer.contains("newer than this build"), "{}", newer);
+    }
+
+    #[test]
+    fn parse_fails_only_for_the_whole_document() {
+        let error = |contents: &str| parse(contents, None).err().unwrap();
+        assert!(error("not json").contains("not valid JSON"));
+        assert!(error(r#"{ "version": 1, "processes": {} }"#).contains("must be a list"));
+        let empty = parse(r#"{ "version": 1 }"#, None).unwrap();
+        assert!(empty.processes.is_empty() && empty.issues.is_empty());
+        assert_eq!(empty.settings.config_backups, AppSettings::default().config_backups);
+    }
+
+    #[test]
+    fn bad_entries_are_reported_and_left_out() {
+        let command = command();
+        let contents = json!({
+            "version": 1,
+            "processes": [
+                { "id": "a", "name": "api", "command": command, "colour": "red" },
+                { "id": "b", "name": "bad", "command": command, "stop_timeout_ms": "soon" },
+                { "id": "c", "name": "blank", "command": "  " },
+                { "id": "a", "name": "copy", "command": command },
+                { "command": command },
+                "not an object"
+            ],
+            "settings": { "config_backups": "many" }
+        })
+        .to_string();
+        let loaded = parse(&contents, None).unwrap();
+
+        let names: Vec<&str> = loaded.processes.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["api"]);
+        assert_eq!(loaded.settings.config_backups, AppSettings::default().config_backups);
+
+        let errors = messages(&loaded.issues, IssueSeverity::Error);
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].starts_with("-/settings.config_backups: "), "{:?}", errors);
+        assert!(errors[1].starts_with("bad/stop_timeout_ms: "), "{:?}", errors);
+        assert_eq!(errors[2], "blank/command: command is empty");
+        assert_eq!(errors[3], "copy/id: id a is already used by another process");
+        assert!(errors[4].starts_with("#5/-: missing field `name`"), "{:?}", errors);
+        assert!(errors[5].starts_with("#6/-: "), "{:?}", errors);
+        assert_eq!(messages(&loaded.issues, IssueSeverity::Warning), ["api/colour: unknown field, ignored"]);
+    }
+
+    #[test]
+    fn unresolved_dependencies_are_kept_and_reported() {
+        let command = command();
+        let contents = json!({
+            "version": 1,
+            "processes": [
+                { "id": "1", "name": "web", "command": command, "depends_on": ["api", "ghost", "worker", "3"] },
+                { "id": "2", "name": "api", "command": command },
+                { "id": "3", "name": "worker", "command": command },
+                { "id": "4", "name": "worker", "command": command },
+            ]
+        })
+        .to_string();
+        let loaded = parse(&contents, None).unwrap();
+        assert_eq!(loaded.processes[0].depends_on, ["api", "ghost", "worker", "3"]);
+        let mut warnings = messages(&loaded.issues, IssueSeverity::Warning);
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                "web/depends_on: ambiguous dependency 'worker' is ignored",
+                "web/depends_on: unknown dependency 'ghost' is ignored",
+                "worker/name: name is used by more than one process; refer to them by id",
+            ]
+        );
+        assert!(messages(&loaded.issues, IssueSeverity::Error).is_empty());
+    }
+
+    #[test]
+    fn filesystem_problems_are_warnings() {
+        let contents = json!({
+            "version": 1,
+            "processes": [{
+                "id": "1", "name": "svc", "command": "pm-surely-not-a-program",
+                "working_dir": "/pm/surely/missing", "env_file": ".env"
+            }]
+        })
+        .to_string();
+        let loaded = parse(&contents, None).unwrap();
+        assert_eq!(loaded.processes.len(), 1);
+        let fields: Vec<&str> = loaded.issues.iter().filter_map(|i| i.field.as_deref()).collect();
+        assert_eq!(fields, ["command", "working_dir", "env_file"]);
+        assert!(loaded.issues.iter().all(|i| i.severity == IssueSeverity::Warning));
+    }
+
+    #[test]
+    fn preserved_keeps_settings_and_rejected_entries_verbatim() {
+        let bad = json!({ "id": "b", "name": "bad", "command": "x", "stop_timeout_ms": "soon" });
+        let doc = json!({
+            "version": 1,
+            "processes": [{ "id": "a", "name": "ok", "command": "x" }, bad.clone(), { "id": "c", "name": "blank", "command": "" }],
+            "settings": { "config_backups": "many", "future_setting": true }
+        });
+        let (settings, rejected) = preserved(doc, None).unwrap();
+        assert_eq!(settings, json!({ "config_backups": "many", "future_setting": true }));
+        assert_eq!(rejected, [bad, json!({ "id": "c", "name": "blank", "command": "" })]);
+
+        let (settings, rejected) = preserved(json!([{ "name": "no command" }]), Some(json!({ "legacy": 1 }))).unwrap();
+        assert_eq!(settings, json!({ "legacy": 1 }));
+        assert_eq!(rejected, [json!({ "name": "no command" })]);
+        assert!(preserved(json!({ "version": CONFIG_VERSION + 1 }), None).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Notices edits made outside the app to `processes.json` and the open
+//! project files. Editors often save by writing a temp file and renaming
+//! it over the original, so the directories are watched, not the files.
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Events closer together than this are one edit.
+const SETTLE: Duration = Duration::from_millis(300);
+
+pub struct ConfigWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    dirs: HashSet<PathBuf>,
+}
+
+/// Whether `event_path` is `file`, allowing for one of them going through
+/// a symlink (macOS reports `/private/var` for `/var`).
+fn same_file(event_path: &Path, file: &Path) -> bool {
+    if event_path == file {
+        return true;
+    }
+    let canonical_dir = |p: &Path| p.parent().and_then(|dir| dir.canonicalize().ok());
+    event_path.file_name() == file.file_name() && canonical_dir(event_path).is_some_and(|d| Some(d) == canonical_dir(file))
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Result<Self, String> {
+        let (tx, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx).map_err(|e| e.to_string())?;
+        Ok(ConfigWatcher {
+            watcher,
+            events,
+            dirs: HashSet::new(),
+        })
+    }
+
+    /// Watch the directories holding `files`, and stop watching the rest.
+    /// A directory that can't be watched yet is tried again next call.
+    pub fn watch(&mut self, files: &[PathBuf]) {
+        let wanted: HashSet<PathBuf> = files
+            .iter()
+            .filter_map(|file| file.parent())
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in self.dirs.difference(&wanted) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        let watched = wanted
+            .into_iter()
+            .filter(|dir| self.dirs.contains(dir) || self.watcher.watch(dir, RecursiveMode::NonRecursive).is_ok())
+            .collect();
+        self.dirs = watched;
+    }
+
+    /// Wait up to `timeout` for an edit and until it settles, then return
+    /// which of `files` it touched.
+    pub fn changed(&self, files: &[PathBuf], timeout: Duration) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut wait = timeout;
+        loop {
+            match self.events.recv_timeout(wait) {
+                Ok(Ok(event)) if !event.kind.is_access() => {
+                    for file in files {
+                        if !changed.contains(file) && event.paths.iter().any(|p| same_file(p, file)) {
+                            changed.push(file.clone());
+                        }
+                    }
+                    wait = SETTLE;
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => return changed,
+                Err(RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(timeout);
+                    return changed;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Headless daemon exposing the process operations over a local socket.
+//!
+//! The protocol is line-delimited JSON over a Unix domain socket:
+//!
+//! ```text
+//! -> {"id": 1, "method": "start_process", "params": {"process_id": "..."}}
+//! <- {"id": 1, "result": 4242}
+//! <- {"id": 2, "error": "Process not found"}
+//! ```
+//!
+//! Parameter names match the Tauri commands in snake_case; `process_id`
+//! also accepts a unique process name. Sending
+//! `{"method": "subscribe"}` turns the connection into a stream of
+//! `{"event": "...", "payload": ...}` lines carrying every backend event.
+
+use crate::config_handler::ConfigHandler;
+use crate::service::{self, AppState, EventSink, Events, ProcessUpdate};
+use crate::types::{LogExportFormat, LogQuery, MetricsResolution};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+
+fn socket_path() -> Result<PathBuf, String> {
+    ConfigHandler::get_socket_path().map_err(|e| e.to_string())
+}
+
+/// Client side of the daemon socket. Each call opens its own connection.
+pub struct DaemonClient {
+    socket_path: PathBuf,
+}
+
+impl DaemonClient {
+    pub fn new(socket_path: PathBuf) -> Self {
+        DaemonClient { socket_path }
+    }
+
+    /// Invoke `method` and deserialize its result.
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        let path = self.socket_path.clone();
+        let method = method.to_string();
+        let result = tauri::async_runtime::spawn_blocking(move || request(&path, &method, params))
+            .await
+            .map_err(|e| e.to_string())??;
+        serde_json::from_value(result).map_err(|e| format!("Invalid daemon response: {}", e))
+    }
+
+    /// Blocking variant of [`Self::call`] for non-async callers.
+    pub fn call_blocking<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        let result = request(&self.socket_path, method, params)?;
+        serde_json::from_value(result).map_err(|e| format!("Invalid daemon response: {}", e))
+    }
+
+    pub fn ping(&self) -> bool {
+        request(&self.socket_path, "ping", json!({})).is_ok()
+    }
+
+    /// Stream daemon events to `on_event` until the connection closes.
+    #[cfg(unix)]
+    pub fn subscribe(&self, mut on_event: impl FnMut(String, Value)) -> Result<(), String> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(|e| e.to_string())?;
+        writeln!(stream, "{}", json!({ "id": 0, "method": "subscribe" })).map_err(|e| e.to_string())?;
+
+        let reader = BufReader::new(stream);
+        for line in reader.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if let Some(event) = message.get("event").and_then(Value::as_str) {
+                let payload = message.get("payload").cloned().unwrap_or(Value::Null);
+                on_event(event.to_string(), payload);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn request(path: &std::path::Path, method: &str, params: Value) -> Result<Value, String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| format!("Failed to connect to daemon at {}: {}", path.display(), e))?;
+    writeln!(stream, "{}", json!({ "id": 1, "method": method, "params": params }))
+        .map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|e| format!("Invalid daemon response: {}", e))?;
+
+    match response.get("error") {
+        Some(error) => Err(error.as_str().map(str::to_string).unwrap_or_else(|| error.to_string())),
+        None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+#[cfg(not(unix))]
+fn request(_path: &std::path::Path, _method: &str, _params: Value) -> Result<Value, String> {
+    Err("Daemon mode is only supported on Unix".to_string())
+}
+
+/// Connect to a running daemon, starting one in the background if none is
+/// listening. Returns `None` to run in-process instead (set `PM_NO_DAEMON`
+/// to force that).
+pub fn connect_or_spawn() -> Option<DaemonClient> {
+    if std::env::var_os("PM_NO_DAEMON").is_some() {
+        return None;
+    }
+    let client = DaemonClient::new(socket_path().ok()?);
+    if client.ping() {
+        return Some(client);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        use std::process::{Command, Stdio};
+
+        let exe = std::env::current_exe().ok()?;
+        // Own process group so the daemon outlives the GUI's.
+        Command::new(exe)
+            .arg("--daemon")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .ok()?;
+
+        for _ in 0..30 {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            if client.ping() {
+                return Some(client);
+            }
+        }
+    }
+
+    None
+}
+
+/// Fans events out to every subscribed connection.
+#[derive(Default)]
+struct Subscribers(Mutex<Vec<mpsc::Sender<String>>>);
+
+impl Subscribers {
+    fn add(&self, sender: mpsc::Sender<String>) {
+        if let Ok(mut subs) = self.0.lock() {
+            subs.push(sender);
+        }
+    }
+}
+
+impl EventSink for Subscribers {
+    fn emit(&self, event: &str, payload: Value) {
+        let line = json!({ "event": event, "payload": payload }).to_string();
+        if let Ok(mut subs) = self.0.lock() {
+            // Disconnected subscribers drop their receiver.
+            subs.retain(|tx| tx.send(line.clone()).is_ok());
+        }
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &Value, key: &str) -> Result<T, String> {
+    serde_json::from_value(params.get(key).cloned().unwrap_or(Value::Null))
+        .map_err(|e| format!("Invalid parameter '{}': {}", key, e))
+}
+
+/// The `process_id` parameter, resolved from an id or a unique name.
+fn process_id(state: &AppState, params: &Value) -> Result<String, String> {
+    service::resolve_process(state, &param::<String>(params, "process_id")?)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+/// Run one request against the in-process service.
+async fn dispatch(state: &AppState, events: Events, method: &str, params: Value) -> Result<Value, String> {
+    let p = &params;
+    match method {
+        "ping" => Ok(json!("pong")),
+        "resolve_process" => to_value(process_id(state, p)?),
+        "get_processes" => to_value(service::get_processes(state)?),
+        "start_process" => to_value(
+            service::start_process(state, process_id(state, p)?, param(p, "with_dependencies")?, events).await?,
+        ),
+        "stop_process" => to_value(service::stop_process(state, process_id(state, p)?, events).await?),
+        "restart_process" => to_value(service::restart_process(state, process_id(state, p)?, events).await?),
+        "add_process" => to_value(service::add_process(
+            state,
+            param(p, "name")?,
+            param(p, "command")?,
+            param::<Option<Vec<String>>>(p, "args")?.unwrap_or_default(),
+            param(p, "working_dir")?,
+            param(p, "env")?,
+            param(p, "env_file")?,
+        )?),
+        "import_processes" => to_value(service::import_processes(
+            state,
+            &param::<String>(p, "path")?,
+            param(p, "format")?,
+            param::<Option<bool>>(p, "run_js")?.unwrap_or(false),
+        )?),
+        "remove_process" => to_value(service::remove_process(state, process_id(state, p)?, events).await?),
+        "update_process" => {
+            let update: ProcessUpdate = serde_json::from_value(params.clone()).map_err(|e| e.to_string())?;
+            to_value(service::update_process(state, &process_id(state, p)?, update)?)
+        }
+        "get_metrics" => to_value(service::get_metrics(state, &process_id(state, p)?)?),
+        "get_metrics_history" => to_value(service::get_metrics_history(
+            state,
+            &process_id(state, p)?,
+            param(p, "from")?,
+            param(p, "to")?,
+            param::<Option<MetricsResolution>>(p, "resolution")?.unwrap_or_default(),
+        )?),
+        "get_logs" => to_value(service::get_logs(state, &process_id(state, p)?)?),
+        "query_logs" => to_value(service::query_logs(
+            state,
+            &process_id(state, p)?,
+            &param::<Option<LogQuery>>(p, "query")?.unwrap_or_default(),
+        )?),
+        "export_logs" => to_value(service::export_logs(
+            state,
+            &process_id(state, p)?,
+            &param::<Option<LogQuery>>(p, "query")?.unwrap_or_default(),
+            param::<Option<LogExportFormat>>(p, "format")?.unwrap_or_default(),
+            &param::<String>(p, "path")?,
+        )?),
+        "create_support_bundle" => {
+            let process_ids = param::<Option<Vec<String>>>(p, "process_ids")?
+                .unwrap_or_default()
+                .iter()
+                .map(|reference| service::resolve_process(state, reference))
+                .collect::<Result<Vec<_>, _>>()?;
+            to_value(service::create_support_bundle(
+                state,
+                &process_ids,
+                param(p, "since")?,
+                &param::<String>(p, "path")?,
+            )?)
+        }
+        "clear_logs" => to_value(service::clear_logs(state, &process_id(state, p)?)?),
+        "save_config" => to_value(service::save_config(
+            state,
+            param::<Option<bool>>(p, "force")?.unwrap_or(false),
+        )?),
+        "load_config" => to_value(service::load_config(state, events).await?),
+        "check_config" => to_value(service::check_config()?),
+        "list_config_backups" => to_value(service::list_config_backups()?),
+        "restore_config_backup" => {
+            to_value(service::restore_config_backup(state, &param::<String>(p, "name")?, events).await?)
+        }
+        "start_all" => to_value(service::start_all(state, events).await?),
+        "stop_all" => to_value(service::stop_all(state, events).await?),
+        "open_workspace" => to_value(service::open_workspace(state, &param::<String>(p, "path")?)?),
+        "close_workspace" => to_value(service::close_workspace(state, &param::<String>(p, "name")?, events).await?),
+        "list_workspaces" => to_value(service::list_workspaces(state)?),
+        "workspace_up" => to_value(service::workspace_up(state, &param::<String>(p, "name")?, events).await?),
+        "workspace_down" => to_value(service::workspace_down(state, &param::<String>(p, "name")?, events).await?),
+        _ => Err(format!("Unknown method '{}'", method)),
+    }
+}
+
+#[cfg(unix)]
+fn handle_connection(stream: std::os::unix::net::UnixStream, state: AppState, subscribers: std::sync::Arc<Subscribers>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let Ok(read_half) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+
+    for line in BufReader::new(read_half).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(v) => v,
+            Err(e) => {
+                let _ = writeln!(writer, "{}", json!({ "id": null, "error": format!("Invalid request: {}", e) }));
+                continue;
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+
+        if method == "subscribe" {
+            let (tx, rx) = mpsc::channel();
+            subscribers.add(tx);
+            if writeln!(writer, "{}", json!({ "id": id, "result": null })).is_err() {
+                return;
+            }
+            for event in rx {
+                if writeln!(writer, "{}", event).is_err() {
+                    return;
+                }
+            }
+            return;
+        }
+
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+        let events: Events = subscribers.clone();
+        let response = match tauri::async_runtime::block_on(dispatch(&state, events, method, params)) {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(error) => json!({ "id": id, "error": error }),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            break;
+        }
+    }
+}
+
+/// Bind the control socket at `path` with owner-only permissions.
+///
+/// Anyone who can connect can run commands as this user, so the socket must
+/// never be reachable with wider permissions, not even between bind and
+/// chmod. It is bound inside a fresh 0700 directory, restricted, and only
+/// then moved into place; the process-wide umask is left alone since other
+/// threads are already running.
+#[cfg(unix)]
+fn bind_private(path: &std::path::Path) -> std::io::Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    let staging = path.with_file_name(format!(".bind-{}", std::process::id()));
+    // Left behind by an earlier daemon that had the same pid.
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let bind = || {
+        let bound = staging.join("socket");
+        let listener = UnixListener::bind(&bound)?;
+        std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound, path)?;
+        Ok(listener)
+    };
+    let result = bind();
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+/// Run the daemon in the foreground: load the saved processes, start the
+/// auto-start ones, and serve the control socket until killed.
+#[cfg(unix)]
+pub fn serve(state: AppState) -> Result<(), String> {
+    use std::sync::Arc;
+
+    let path = socket_path()?;
+    if path.exists() {
+        if DaemonClient::new(path.clone()).ping() {
+            return Err(format!("A daemon is already listening on {}", path.display()));
+        }
+        // Stale socket left by a daemon that died.
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+
+    let listener = bind_private(&path).map_err(|e| format!("Failed to bind {}: {}", path.display(), e))?;
+
+    let subscribers = Arc::new(Subscribers::default());
+
+    if let Err(e) = tauri::async_runtime::block_on(service::load_config(&state, subscribers.clone())) {
+        eprintln!("Failed to load processes.json: {}", e);
+    }
+    {
+        let state = state.clone();
+        let events: Events = subscribers.clone();
+        std::thread::spawn(move || {
+            let _ = tauri::async_runtime::block_on(service::start_auto_start(&state, events));
+        });
+    }
+    service::start_metrics_sampler(&state, subscribers.clone());
+    service::start_config_watcher(&state, subscribers.clone());
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let state = state.clone();
+        let subscribers = Arc::clone(&subscribers);
+        std::thread::spawn(move || handle_connection(stream, state, subscribers));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_state: AppState) -> Result<(), String> {
+    Err("Daemon mode is only supported on Unix".to_string())
+}
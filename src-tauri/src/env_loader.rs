@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build the environment overrides for a spawned process.
+///
+/// Variables from `env_file` are applied first, then the explicit `env`
+/// map. `${VAR}` references are expanded against variables defined earlier
+/// and then the parent environment; unknown variables expand to "".
+pub fn resolve_env(
+    env: &HashMap<String, String>,
+    env_file: Option<&str>,
+    working_dir: Option<&str>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut resolved: Vec<(String, String)> = Vec::new();
+
+    if let Some(file) = env_file {
+        let path = resolve_path(file, working_dir);
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read env file {}: {}", path.display(), e))?;
+        for (key, value) in parse_dotenv(&contents)? {
+            let value = interpolate(&value, &resolved);
+            set_var(&mut resolved, key, value);
+        }
+    }
+
+    // Sort so interpolation between entries of the map is deterministic.
+    let mut keys: Vec<&String> = env.keys().collect();
+    keys.sort();
+    for key in keys {
+        let value = interpolate(&env[key], &resolved);
+        set_var(&mut resolved, key.clone(), value);
+    }
+
+    Ok(resolved)
+}
+
+fn resolve_path(file: &str, working_dir: Option<&str>) -> PathBuf {
+    let path = Path::new(file);
+    match working_dir {
+        Some(dir) if path.is_relative() => Path::new(dir).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn set_var(vars: &mut Vec<(String, String)>, key: String, value: String) {
+    if let Some(existing) = vars.iter_mut().find(|(k, _)| *k == key) {
+        existing.1 = value;
+    } else {
+        vars.push((key, value));
+    }
+}
+
+/// Parse dotenv syntax: `KEY=value`, optional `export ` prefix, `#` comments,
+/// and single- or double-quoted values. Single-quoted values are literal.
+pub fn parse_dotenv(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+
+    for (index, raw) in contents.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid env file line {}: {}", index + 1, raw))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("Invalid variable name on line {}: {}", index + 1, key));
+        }
+
+        let value = value.trim();
+        let value = if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+            // Escape `$` so literal values survive interpolation.
+            inner.replace('$', "$$")
+        } else if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            inner.replace("\\n", "\n").replace("\\\"", "\"")
+        } else {
+            // Unquoted: strip trailing ` # comment`
+            match value.find(" #") {
+                Some(pos) => value[..pos].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Expand `${VAR}` and `$VAR` references. `$$` yields a literal `$`.
+pub fn interpolate(value: &str, defined: &[(String, String)]) -> String {
+    let lookup = |name: &str| -> String {
+        defined
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+            .or_else(|| std::env::var(name).ok())
+            .unwrap_or_default()
+    };
+
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                out.push_str(&lookup(&name));
+            }
+            Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '_' || c.is_ascii_alphanumeric() {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                out.push_str(&lookup(&name));
+            }
+            _ => out.push('$'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_dotenv_syntax() {
+        let contents = "\
+# comment
+export API_URL=http://localhost:8080
+PLAIN = value # trailing comment
+HASH=a#b
+SINGLE='literal $HOME # kept'
+DOUBLE=\"line\\nbreak \\\"quoted\\\"\"
+EMPTY=
+
+";
+        let parsed = parse_dotenv(contents).unwrap();
+        assert_eq!(
+            parsed,
+            vars(&[
+                ("API_URL", "http://localhost:8080"),
+                ("PLAIN", "value"),
+                ("HASH", "a#b"),
+                ("SINGLE", "literal $$HOME # kept"),
+                ("DOUBLE", "line\nbreak \"quoted\""),
+                ("EMPTY", ""),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse_dotenv("NO_EQUALS").unwrap_err().contains("line 1"));
+        assert!(parse_dotenv("A=1\nBAD KEY=2").unwrap_err().contains("line 2"));
+        assert!(parse_dotenv("=value").is_err());
+    }
+
+    #[test]
+    fn interpolates_defined_then_parent_env() {
+        let defined = vars(&[("HOST", "db"), ("PORT", "5432"), ("HOST", "db2")]);
+        assert_eq!(interpolate("${HOST}:$PORT/x", &defined), "db2:5432/x");
+        assert_eq!(interpolate("$PM_TEST_SURELY_UNSET_VAR!", &defined), "!");
+        assert_eq!(interpolate("cost: $$5, $1 and $", &defined), "cost: $5, $1 and $");
+        let path = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(interpolate("${PATH}", &[]), path);
+    }
+
+    #[test]
+    fn resolve_env_applies_file_then_map() {
+        let dir = std::env::temp_dir().join(format!("pm-env-loader-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".env"), "BASE=/srv\nDATA=${BASE}/data\nRAW='$BASE'\n").unwrap();
+
+        let env = HashMap::from([
+            ("DATA".to_string(), "$BASE/override".to_string()),
+            ("LOGS".to_string(), "${DATA}/logs".to_string()),
+        ]);
+        let resolved = resolve_env(&env, Some(".env"), dir.to_str()).unwrap();
+        assert_eq!(
+            resolved,
+            vars(&[("BASE", "/srv"), ("DATA", "/srv/override"), ("RAW", "$BASE"), ("LOGS", "/srv/override/logs")])
+        );
+
+        let missing = resolve_env(&HashMap::new(), Some("missing.env"), dir.to_str()).unwrap_err();
+        assert!(missing.contains("missing.env"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
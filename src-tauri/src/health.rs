@@ -0,0 +1,130 @@
+use crate::types::HealthProbe;
+use regex::Regex;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+/// Watches captured output for a health-check pattern. Log readers call
+/// [`LogProbe::observe`]; the health monitor consumes the match flag.
+pub struct LogProbe {
+    pattern: Regex,
+    seen: AtomicBool,
+}
+
+impl LogProbe {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let pattern = Regex::new(pattern).map_err(|e| format!("Invalid log pattern: {}", e))?;
+        Ok(LogProbe {
+            pattern,
+            seen: AtomicBool::new(false),
+        })
+    }
+
+    pub fn observe(&self, line: &str) {
+        if self.pattern.is_match(line) {
+            self.seen.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether a matching line was seen since the last call.
+    pub fn take_seen(&self) -> bool {
+        self.seen.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Run one probe; true means the check passed within `timeout`.
+pub fn run_probe(
+    probe: &HealthProbe,
+    timeout: Duration,
+    working_dir: Option<&str>,
+    log_probe: Option<&LogProbe>,
+) -> bool {
+    match probe {
+        HealthProbe::Tcp { port } => connect_localhost(*port, timeout).is_some(),
+        HealthProbe::Http { port, path } => http_get_ok(*port, path, timeout),
+        HealthProbe::LogLine { .. } => log_probe.is_some_and(|p| p.take_seen()),
+        HealthProbe::Exec { command, args } => exec_ok(command, args, working_dir, timeout),
+    }
+}
+
+fn connect_localhost(port: u16, timeout: Duration) -> Option<TcpStream> {
+    // "localhost" may resolve to ::1 before 127.0.0.1; servers bind either.
+    let addrs: Vec<SocketAddr> = ("localhost", port).to_socket_addrs().ok()?.collect();
+    addrs
+        .iter()
+        .find_map(|addr| TcpStream::connect_timeout(addr, timeout).ok())
+}
+
+fn http_get_ok(port: u16, path: &str, timeout: Duration) -> bool {
+    let Some(mut stream) = connect_localhost(port, timeout) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+
+    let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost:{}\r\nConnection: close\r\n\r\n",
+        path, port
+    );
+    if stream.write_all(request.as_bytes()).is_err() {
+        return false;
+    }
+
+    // Only the status line matters: "HTTP/1.1 200 OK"
+    let mut buf = [0u8; 64];
+    let mut len = 0;
+    while len < buf.len() {
+        match stream.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => {
+                len += n;
+                if buf[..len].contains(&b'\n') {
+                    break;
+                }
+            }
+            Err(_) => return false,
+        }
+    }
+    String::from_utf8_lossy(&buf[..len])
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .is_some_and(|code| (200..300).contains(&code))
+}
+
+fn exec_ok(command: &str, args: &[String], working_dir: Option<&str>, timeout: Duration) -> bool {
+    let mut cmd = Command::new(command);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+
+    // Hide console window on Windows
+    #[cfg(target_os = "windows")]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+    let Ok(mut child) = cmd.spawn() else {
+        return false;
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
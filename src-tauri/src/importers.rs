@@ -0,0 +1,667 @@
+//! Importers that translate other tools' process definitions into
+//! processes: docker-compose services, Procfiles and PM2 ecosystem files.
+//! Anything without an equivalent here is reported as a warning instead of
+//! being dropped silently.
+
+use crate::process_manager::ProcessInstance;
+use crate::types::{Backoff, ImportFormat, LimitAction, ProcessConfig, StopSignal};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+pub struct Imported {
+    pub format: ImportFormat,
+    pub processes: Vec<ProcessConfig>,
+    pub warnings: Vec<String>,
+}
+
+/// Guess the format from the file name.
+pub fn detect(path: &Path) -> Option<ImportFormat> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let yaml = name.ends_with(".yml") || name.ends_with(".yaml");
+    if name.starts_with("procfile") {
+        Some(ImportFormat::Procfile)
+    } else if name.starts_with("ecosystem") || name.ends_with(".config.js") || name.ends_with(".config.cjs") || name.ends_with(".json") {
+        Some(ImportFormat::Pm2)
+    } else if yaml {
+        Some(ImportFormat::Compose)
+    } else {
+        None
+    }
+}
+
+/// Read `path` as `format`, or the format its name suggests. JavaScript
+/// ecosystem files are only evaluated when `run_js` is set.
+pub fn import(path: &Path, format: Option<ImportFormat>, run_js: bool) -> Result<Imported, String> {
+    let path = std::path::absolute(path).map_err(|e| e.to_string())?;
+    let format = format
+        .or_else(|| detect(&path))
+        .ok_or_else(|| format!("Can't tell the format of {}; choose one explicitly", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("/"));
+
+    let mut imported = Imported {
+        format,
+        processes: Vec::new(),
+        warnings: Vec::new(),
+    };
+    match format {
+        ImportFormat::Procfile => procfile(&read(&path)?, dir, &mut imported),
+        ImportFormat::Compose => compose(&parse_yaml(&read(&path)?)?, dir, &mut imported)?,
+        ImportFormat::Pm2 => pm2(&read_ecosystem(&path, run_js)?, dir, &mut imported)?,
+    }
+    Ok(imported)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn parse_yaml(contents: &str) -> Result<Value, String> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(contents).map_err(|e| format!("Invalid YAML: {}", e))?;
+    value.apply_merge().map_err(|e| format!("Invalid YAML: {}", e))?;
+    serde_json::to_value(value).map_err(|e| format!("Unsupported YAML: {}", e))
+}
+
+fn new_process(name: &str, mut argv: Vec<String>, dir: &Path) -> ProcessConfig {
+    let command = argv.remove(0);
+    let mut config = ProcessInstance::new(name.to_string(), command, argv, false, false).to_config();
+    config.working_dir = Some(dir.to_string_lossy().to_string());
+    config
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// A string split into words, or a list of strings.
+fn words(value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::String(s) => shell_words::split(s).map_err(|e| format!("can't split '{}': {}", s, e)),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| text(item).ok_or_else(|| format!("expected a string, got {}", item)))
+            .collect(),
+        Value::Null => Ok(Vec::new()),
+        other => Err(format!("expected a string or list, got {}", other)),
+    }
+}
+
+/// The program and arguments for a shell command line. Lines using shell
+/// syntax (variables, pipes, `VAR=value` prefixes, ...) run through the
+/// shell; plain ones are split and run directly.
+fn command_line(line: &str) -> Vec<String> {
+    const SHELL_SYNTAX: &[char] = &['$', '&', '|', ';', '<', '>', '(', ')', '`', '*', '?', '~', '\n'];
+    if !line.contains(SHELL_SYNTAX) {
+        if let Ok(words) = shell_words::split(line) {
+            if words.first().is_some_and(|program| !program.contains('=')) {
+                return words;
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    return vec!["powershell".into(), "-NoProfile".into(), "-Command".into(), line.into()];
+    #[cfg(not(target_os = "windows"))]
+    return vec!["sh".into(), "-c".into(), line.into()];
+}
+
+/// Compose-style durations such as `10s`, `1m30s` or `500ms`.
+fn duration_ms(value: &Value) -> Option<u64> {
+    if let Some(ms) = value.as_u64() {
+        return Some(ms);
+    }
+    let s = value.as_str()?.trim();
+    let re = regex::Regex::new(r"(\d+(?:\.\d+)?)(us|ms|s|m|h)").ok()?;
+    let mut total = 0.0;
+    let mut consumed = 0;
+    for caps in re.captures_iter(s) {
+        let whole = caps.get(0)?;
+        if whole.start() != consumed {
+            return None;
+        }
+        consumed = whole.end();
+        let n: f64 = caps[1].parse().ok()?;
+        total += n * match &caps[2] {
+            "us" => 0.001,
+            "ms" => 1.0,
+            "s" => 1000.0,
+            "m" => 60_000.0,
+            _ => 3_600_000.0,
+        };
+    }
+    (consumed == s.len() && consumed > 0).then_some(total as u64)
+}
+
+/// `name: command` lines; a `.env` next to the Procfile is loaded like
+/// foreman does.
+fn procfile(contents: &str, dir: &Path, out: &mut Imported) {
+    let env_file = dir.join(".env");
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, command)) = line.split_once(':') else {
+            out.warnings.push(format!("line {}: expected `name: command`", n + 1));
+            continue;
+        };
+        let (name, command) = (name.trim(), command.trim());
+        if name.is_empty() || name.contains(char::is_whitespace) || command.is_empty() {
+            out.warnings.push(format!("line {}: expected `name: command`", n + 1));
+            continue;
+        }
+
+        let mut config = new_process(name, command_line(command), dir);
+        if env_file.is_file() {
+            config.env_file = Some(env_file.to_string_lossy().to_string());
+        }
+        out.processes.push(config);
+    }
+}
+
+/// The `command`, `entrypoint`, `environment`, `env_file`, `working_dir`,
+/// `depends_on`, `restart` and stop settings of each service. Services
+/// that only name an image have nothing to run outside a container.
+fn compose(file: &Value, dir: &Path, out: &mut Imported) -> Result<(), String> {
+    let services = file
+        .get("services")
+        .and_then(Value::as_object)
+        .ok_or("No `services` in compose file")?;
+    for key in file.as_object().into_iter().flat_map(|o| o.keys()) {
+        // `x-` keys are extension fields, usually holding YAML anchors.
+        if !matches!(key.as_str(), "services" | "version" | "name") && !key.starts_with("x-") {
+            out.warnings.push(format!("unsupported top-level `{}`", key));
+        }
+    }
+
+    let mut dependencies: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    for (service, spec) in services {
+        let Some(spec) = spec.as_object() else {
+            out.warnings.push(format!("{}: skipped, not a mapping", service));
+            continue;
+        };
+        let argv = match (spec.get("entrypoint"), spec.get("command")) {
+            (None, None) => Vec::new(),
+            (entrypoint, command) => {
+                let mut argv = words(entrypoint.unwrap_or(&Value::Null)).map_err(|e| format!("{}: entrypoint {}", service, e))?;
+                argv.extend(words(command.unwrap_or(&Value::Null)).map_err(|e| format!("{}: command {}", service, e))?);
+                argv
+            }
+        };
+        if argv.is_empty() {
+            out.warnings.push(format!("{}: skipped, no `command` to run outside a container", service));
+            continue;
+        }
+
+        let mut config = new_process(service, argv, dir);
+        let mut depends_on = Vec::new();
+        for (field, value) in spec {
+            match field.as_str() {
+                "command" | "entrypoint" => {}
+                _ if field.starts_with("x-") => {}
+                "environment" => {
+                    let mut env = HashMap::new();
+                    match value {
+                        Value::Object(vars) => {
+                            // A null value passes the host's variable through,
+                            // which inheriting the environment already does.
+                            env.extend(vars.iter().filter_map(|(k, v)| Some((k.clone(), text(v)?))));
+                        }
+                        Value::Array(items) => {
+                            for item in items.iter().filter_map(Value::as_str) {
+                                if let Some((k, v)) = item.split_once('=') {
+                                    env.insert(k.to_string(), v.to_string());
+                                }
+                            }
+                        }
+                        _ => out.warnings.push(format!("{}: unsupported `environment` value", service)),
+                    }
+                    config.env = Some(env);
+                }
+                "env_file" => {
+                    let files = match value {
+                        Value::Array(items) => items
+                            .iter()
+                            .filter_map(|item| text(item).or_else(|| item.get("path").and_then(text)))
+                            .collect(),
+                        other => text(other).into_iter().collect::<Vec<_>>(),
+                    };
+                    if let Some(first) = files.first() {
+                        config.env_file = Some(dir.join(first).to_string_lossy().to_string());
+                    }
+                    if files.len() > 1 {
+                        out.warnings.push(format!("{}: only the first `env_file` is used", service));
+                    }
+                }
+                "working_dir" => match value.as_str() {
+                    Some(working_dir) => config.working_dir = Some(dir.join(working_dir).to_string_lossy().to_string()),
+                    None => out.warnings.push(format!("{}: unsupported `working_dir` value", service)),
+                },
+                "depends_on" => match value {
+                    Value::Array(items) => depends_on.extend(items.iter().filter_map(text)),
+                    Value::Object(deps) => depends_on.extend(deps.keys().cloned()),
+                    _ => out.warnings.push(format!("{}: unsupported `depends_on` value", service)),
+                },
+                "restart" => match value.as_str().unwrap_or_default().split_once(':') {
+                    Some(("on-failure", retries)) => {
+                        config.auto_restart = true;
+                        if let Ok(retries) = retries.parse() {
+                            config.restart_policy.max_retries = retries;
+                        }
+                    }
+                    _ => config.auto_restart = matches!(value.as_str(), Some("always" | "unless-stopped" | "on-failure")),
+                },
+                "stop_signal" => match value.as_str().map(|s| s.trim_start_matches("SIG")) {
+                    Some("TERM") => config.stop_signal = StopSignal::Term,
+                    Some("INT") => config.stop_signal = StopSignal::Int,
+                    Some("QUIT") => config.stop_signal = StopSignal::Quit,
+                    Some("HUP") => config.stop_signal = StopSignal::Hup,
+                    _ => out.warnings.push(format!("{}: unsupported `stop_signal` {}", service, value)),
+                },
+                "stop_grace_period" => match duration_ms(value) {
+                    Some(ms) => config.stop_timeout_ms = ms,
+                    None => out.warnings.push(format!("{}: unsupported `stop_grace_period` {}", service, value)),
+                },
+                _ => out.warnings.push(format!("{}: unsupported field `{}`", service, field)),
+            }
+        }
+
+        ids.insert(service, config.id.clone());
+        dependencies.push((out.processes.len(), depends_on));
+        out.processes.push(config);
+    }
+
+    for (index, depends_on) in dependencies {
+        let service = out.processes[index].name.clone();
+        for dep in depends_on {
+            match ids.get(dep.as_str()) {
+                Some(id) => out.processes[index].depends_on.push(id.clone()),
+                None => out.warnings.push(format!("{}: dropped dependency on skipped or unknown service `{}`", service, dep)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ecosystem files are usually JavaScript. Evaluating one runs arbitrary code,
+/// so Node is only invoked when the caller allows it.
+fn read_ecosystem(path: &Path, run_js: bool) -> Result<Value, String> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "json" => serde_json::from_str(&read(path)?).map_err(|e| format!("Invalid JSON: {}", e)),
+        "yml" | "yaml" => parse_yaml(&read(path)?),
+        _ if !run_js => Err(format!(
+            "{} is JavaScript and importing it runs its code with Node.js. Allow that explicitly, \
+             or use an ecosystem file in JSON or YAML",
+            path.display()
+        )),
+        _ => {
+            const EVAL: &str = "import(require('url').pathToFileURL(process.argv[1]))\
+                .then(m => process.stdout.write(JSON.stringify(m.default ?? m)))";
+            let mut cmd = Command::new("node");
+            cmd.args(["-e", EVAL]).arg(path);
+            #[cfg(target_os = "windows")]
+            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            let output = cmd
+                .output()
+                .map_err(|e| format!("Reading {} needs Node.js: {}", path.display(), e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "Failed to evaluate {}: {}",
+                    path.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            serde_json::from_slice(&output.stdout).map_err(|e| format!("Invalid ecosystem file: {}", e))
+        }
+    }
+}
+
+/// PM2 sizes such as `300M`, `1G` or a byte count, in MB.
+fn size_mb(value: &Value) -> Option<u64> {
+    let s = text(value)?;
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let n: u64 = number.parse().ok()?;
+    match unit.to_ascii_uppercase().as_str() {
+        "" => Some(n.div_ceil(1024 * 1024)),
+        "K" | "KB" => Some(n.div_ceil(1024)),
+        "M" | "MB" => Some(n),
+        "G" | "GB" => Some(n * 1024),
+        _ => None,
+    }
+}
+
+/// The interpreter PM2 would pick for a script.
+fn default_interpreter(script: &str) -> Option<&'static str> {
+    let extension = Path::new(script).extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "js" | "mjs" | "cjs" => Some("node"),
+        "py" => Some("python"),
+        "sh" => Some("bash"),
+        "rb" => Some("ruby"),
+        "php" => Some("php"),
+        "pl" => Some("perl"),
+        _ => None,
+    }
+}
+
+/// The `apps` of an ecosystem file (or a bare list of apps).
+fn pm2(file: &Value, dir: &Path, out: &mut Imported) -> Result<(), String> {
+    let apps = match file {
+        Value::Array(apps) => apps,
+        Value::Object(o) => {
+            for key in o.keys().filter(|k| *k != "apps") {
+                out.warnings.push(format!("unsupported top-level `{}`", key));
+            }
+            o.get("apps").and_then(Value::as_array).ok_or("No `apps` in ecosystem file")?
+        }
+        _ => return Err("No `apps` in ecosystem file".to_string()),
+    };
+
+    for (i, app) in apps.iter().enumerate() {
+        let Some(spec) = app.as_object() else {
+            out.warnings.push(format!("app {}: skipped, not an object", i + 1));
+            continue;
+        };
+        let Some(script) = spec.get("script").and_then(text) else {
+            out.warnings.push(format!("app {}: skipped, no `script`", i + 1));
+            continue;
+        };
+        let name = spec.get("name").and_then(text).unwrap_or_else(|| {
+            Path::new(&script)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| script.clone())
+        });
+        let field_words = |field: &str| {
+            words(spec.get(field).unwrap_or(&Value::Null)).map_err(|e| format!("{}: {} {}", name, field, e))
+        };
+
+        let interpreter = match spec.get("interpreter").and_then(Value::as_str) {
+            Some("none") => None,
+            Some(interpreter) => Some(interpreter.to_string()),
+            None => default_interpreter(&script).map(str::to_string),
+        };
+        let mut argv = Vec::new();
+        if let Some(interpreter) = interpreter {
+            argv.push(interpreter);
+            argv.extend(field_words("interpreter_args")?);
+            argv.extend(field_words("node_args")?);
+        }
+        argv.push(script.clone());
+        argv.extend(field_words("args")?);
+
+        let mut config = new_process(&name, argv, dir);
+        // PM2 restarts crashed apps unless told otherwise.
+        config.auto_restart = true;
+        for (field, value) in spec {
+            match field.as_str() {
+                "name" | "script" | "args" | "interpreter" | "interpreter_args" | "node_args" => {}
+                "cwd" => match value.as_str() {
+                    Some(cwd) => config.working_dir = Some(dir.join(cwd).to_string_lossy().to_string()),
+                    None => out.warnings.push(format!("{}: unsupported `cwd` value", name)),
+                },
+                "env" => match value.as_object() {
+                    Some(vars) => {
+                        config.env = Some(vars.iter().filter_map(|(k, v)| Some((k.clone(), text(v)?))).collect());
+                    }
+                    None => out.warnings.push(format!("{}: unsupported `env` value", name)),
+                },
+                "autorestart" => config.auto_restart = value.as_bool().unwrap_or(true),
+                "max_restarts" => match value.as_u64() {
+                    Some(n) => config.restart_policy.max_retries = n as u32,
+                    None => out.warnings.push(format!("{}: unsupported `max_restarts` {}", name, value)),
+                },
+                "min_uptime" => match duration_ms(value) {
+                    Some(ms) => config.restart_policy.min_uptime_ms = ms,
+                    None => out.warnings.push(format!("{}: unsupported `min_uptime` {}", name, value)),
+                },
+                "restart_delay" | "exp_backoff_restart_delay" => match value.as_u64() {
+                    Some(ms) => {
+                        config.restart_policy.delay_ms = ms;
+                        config.restart_policy.backoff = if field == "restart_delay" {
+                            Backoff::Fixed
+                        } else {
+                            Backoff::Exponential
+                        };
+                    }
+                    None => out.warnings.push(format!("{}: unsupported `{}` {}", name, field, value)),
+                },
+                "kill_timeout" => match value.as_u64() {
+                    Some(ms) => config.stop_timeout_ms = ms,
+                    None => out.warnings.push(format!("{}: unsupported `kill_timeout` {}", name, value)),
+                },
+                "max_memory_restart" => match size_mb(value) {
+                    Some(mb) => {
+                        config.limits.max_memory_mb = Some(mb);
+                        config.limits.action = LimitAction::Restart;
+                    }
+                    None => out.warnings.push(format!("{}: unsupported `max_memory_restart` {}", name, value)),
+                },
+                "instances" if matches!(value.as_u64(), Some(1)) => {}
+                "exec_mode" if value.as_str() == Some("fork") => {}
+                _ => out.warnings.push(format!("{}: unsupported field `{}`", name, field)),
+            }
+        }
+        out.processes.push(config);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn imported(format: ImportFormat) -> Imported {
+        Imported {
+            format,
+            processes: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn find<'a>(out: &'a Imported, name: &str) -> &'a ProcessConfig {
+        out.processes.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn detects_format_from_name() {
+        let detect = |name: &str| detect(Path::new(name));
+        assert!(matches!(detect("Procfile.dev"), Some(ImportFormat::Procfile)));
+        assert!(matches!(detect("ecosystem.config.js"), Some(ImportFormat::Pm2)));
+        assert!(matches!(detect("pm2.json"), Some(ImportFormat::Pm2)));
+        assert!(matches!(detect("compose.yaml"), Some(ImportFormat::Compose)));
+        assert!(matches!(detect("docker-compose.yml"), Some(ImportFormat::Compose)));
+        assert!(detect("notes.txt").is_none());
+    }
+
+    #[test]
+    fn parses_durations_and_sizes() {
+        assert_eq!(duration_ms(&json!("1m30s")), Some(90_000));
+        assert_eq!(duration_ms(&json!("1.5s")), Some(1500));
+        assert_eq!(duration_ms(&json!("250ms")), Some(250));
+        assert_eq!(duration_ms(&json!(42)), Some(42));
+        assert_eq!(duration_ms(&json!("10 s")), None);
+        assert_eq!(duration_ms(&json!("")), None);
+
+        assert_eq!(size_mb(&json!("300M")), Some(300));
+        assert_eq!(size_mb(&json!("1G")), Some(1024));
+        assert_eq!(size_mb(&json!("512K")), Some(1));
+        assert_eq!(size_mb(&json!(1_048_577)), Some(2));
+        assert_eq!(size_mb(&json!("lots")), None);
+    }
+
+    #[test]
+    fn imports_procfile_lines() {
+        let dir = std::env::temp_dir().join(format!("pm-import-procfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".env"), "A=1\n").unwrap();
+
+        let mut out = imported(ImportFormat::Procfile);
+        procfile(
+            "# comment\nweb: bundle exec rails s -p 3000\nworker: FOO=1 sidekiq\nbad line\nspaced name: x\n",
+            &dir,
+            &mut out,
+        );
+        let web = find(&out, "web");
+        assert_eq!((web.command.as_str(), web.args.join(" ").as_str()), ("bundle", "exec rails s -p 3000"));
+        assert_eq!(web.working_dir.as_deref(), dir.to_str());
+        assert_eq!(web.env_file.as_deref(), dir.join(".env").to_str());
+        // Shell syntax runs through the shell.
+        let worker = find(&out, "worker");
+        assert_eq!(worker.args.last().map(String::as_str), Some("FOO=1 sidekiq"));
+        assert_eq!(out.warnings, ["line 4: expected `name: command`", "line 5: expected `name: command`"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn imports_compose_services() {
+        let file = parse_yaml(
+            r#"
+version: "3"
+x-common: &common
+  environment:
+    SHARED: "yes"
+services:
+  db:
+    image: postgres
+    ports: ["5432:5432"]
+  api:
+    <<: *common
+    command: ["npm", "run", "dev"]
+    working_dir: api
+    env_file: [.env, .env.local]
+    depends_on:
+      db: { condition: service_started }
+      cache: {}
+    restart: on-failure:3
+    stop_signal: SIGINT
+    stop_grace_period: 1m30s
+    ports: ["80:80"]
+  cache:
+    entrypoint: redis-server --port 6380
+    restart: unless-stopped
+volumes: {}
+"#,
+        )
+        .unwrap();
+        let dir = Path::new("/srv/app");
+        let mut out = imported(ImportFormat::Compose);
+        compose(&file, dir, &mut out).unwrap();
+
+        assert_eq!(out.processes.len(), 2);
+        let (api, cache) = (find(&out, "api"), find(&out, "cache"));
+        assert_eq!((api.command.as_str(), api.args.as_slice()), ("npm", ["run".to_string(), "dev".into()].as_slice()));
+        assert_eq!(api.working_dir.as_deref(), dir.join("api").to_str());
+        assert_eq!(api.env.as_ref().unwrap()["SHARED"], "yes");
+        assert_eq!(api.env_file.as_deref(), dir.join(".env").to_str());
+        assert_eq!(api.depends_on, vec![cache.id.clone()]);
+        assert!(api.auto_restart);
+        assert_eq!(api.restart_policy.max_retries, 3);
+        assert!(matches!(api.stop_signal, StopSignal::Int));
+        assert_eq!(api.stop_timeout_ms, 90_000);
+        assert_eq!((cache.command.as_str(), cache.args.len()), ("redis-server", 2));
+        assert!(cache.auto_restart);
+
+        let mut warnings = out.warnings.clone();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                "api: dropped dependency on skipped or unknown service `db`",
+                "api: only the first `env_file` is used",
+                "api: unsupported field `ports`",
+                "db: skipped, no `command` to run outside a container",
+                "unsupported top-level `volumes`",
+            ]
+        );
+
+        assert!(compose(&json!({ "version": "3" }), dir, &mut imported(ImportFormat::Compose)).is_err());
+    }
+
+    #[test]
+    fn imports_pm2_apps() {
+        let file = json!({
+            "apps": [
+                {
+                    "name": "api", "script": "server.js", "node_args": "--inspect", "args": ["--port", "80"],
+                    "cwd": "srv", "env": { "PORT": 80 }, "max_memory_restart": "300M",
+                    "exp_backoff_restart_delay": 100, "instances": 4, "watch": true
+                },
+                { "script": "worker.py", "autorestart": false, "kill_timeout": 3000, "restart_delay": 500 },
+                { "name": "bin", "script": "./run", "interpreter": "none", "exec_mode": "fork", "instances": 1 },
+                { "name": "broken" },
+                "not an app"
+            ],
+            "deploy": {}
+        });
+        let dir = Path::new("/srv/app");
+        let mut out = imported(ImportFormat::Pm2);
+        pm2(&file, dir, &mut out).unwrap();
+
+        let api = find(&out, "api");
+        assert_eq!(api.command, "node");
+        assert_eq!(api.args, ["--inspect", "server.js", "--port", "80"]);
+        assert_eq!(api.working_dir.as_deref(), dir.join("srv").to_str());
+        assert_eq!(api.env.as_ref().unwrap()["PORT"], "80");
+        assert_eq!(api.limits.max_memory_mb, Some(300));
+        assert!(matches!(api.limits.action, LimitAction::Restart));
+        assert!(matches!(api.restart_policy.backoff, Backoff::Exponential));
+        assert_eq!(api.restart_policy.delay_ms, 100);
+        assert!(api.auto_restart);
+
+        let worker = find(&out, "worker");
+        assert_eq!((worker.command.as_str(), worker.args.as_slice()), ("python", ["worker.py".to_string()].as_slice()));
+        assert!(!worker.auto_restart);
+        assert_eq!(worker.stop_timeout_ms, 3000);
+        assert!(matches!(worker.restart_policy.backoff, Backoff::Fixed));
+
+        let bin = find(&out, "bin");
+        assert_eq!((bin.command.as_str(), bin.args.len()), ("./run", 0));
+
+        assert_eq!(out.processes.len(), 3);
+        assert_eq!(
+            out.warnings,
+            [
+                "unsupported top-level `deploy`",
+                "api: unsupported field `instances`",
+                "api: unsupported field `watch`",
+                "app 4: skipped, no `script`",
+                "app 5: skipped, not an object",
+            ]
+        );
+
+        // A bare list of apps is accepted too.
+        let mut out = imported(ImportFormat::Pm2);
+        pm2(&json!([{ "script": "app.js" }]), dir, &mut out).unwrap();
+        assert_eq!(out.processes[0].name, "app");
+        assert!(pm2(&json!({ "deploy": {} }), dir, &mut imported(ImportFormat::Pm2)).is_err());
+    }
+
+    #[test]
+    fn javascript_ecosystem_needs_opt_in() {
+        let dir = std::env::temp_dir().join(format!("pm-import-pm2-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let js = dir.join("ecosystem.config.js");
+        std::fs::write(&js, "module.exports = { apps: [] };\n").unwrap();
+        std::fs::write(dir.join("ecosystem.json"), r#"{ "apps": [{ "script": "app.js" }] }"#).unwrap();
+
+        let error = read_ecosystem(&js, false).unwrap_err();
+        assert!(error.contains("JavaScript"), "{}", error);
+        let imported = import(&dir.join("ecosystem.json"), None, false).unwrap();
+        assert_eq!(imported.processes.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
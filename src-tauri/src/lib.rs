@@ -1,52 +1,114 @@
+mod cli;
 mod commands;
 mod config_handler;
+mod config_schema;
+mod config_watcher;
+mod daemon;
+mod env_loader;
+mod health;
+mod importers;
 mod log_handler;
+mod metrics_history;
 mod monitoring;
+mod output_reader;
 mod process_manager;
+mod redact;
+mod service;
+mod support_bundle;
 mod terminal;
+mod triggers;
 mod types;
+mod watchdog;
+mod workspace;
 
-use commands::AppState;
 use config_handler::ConfigHandler;
 use log_handler::LogHandler;
-use process_manager::ProcessManager;
+use metrics_history::MetricsHistory;
+use service::AppState;
 use std::sync::Arc;
-use sysinfo;
 use tauri::{
     menu::{Menu, MenuItem, PredefinedMenuItem},
     tray::TrayIconBuilder,
-    Emitter, Manager,
+    Emitter, Listener, Manager,
 };
+use tauri_plugin_notification::NotificationExt;
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
+fn init_state() -> AppState {
     // Initialize config and log directories
     let log_dir = ConfigHandler::get_logs_dir()
         .expect("Failed to get logs directory");
     let log_handler = LogHandler::new(log_dir)
         .expect("Failed to initialize log handler");
 
-    let process_manager = Arc::new(std::sync::Mutex::new(ProcessManager::new()));
-    let log_handler = Arc::new(log_handler);
-    
-    // Pre-warm sysinfo so the first CPU reading has a prior snapshot to diff against.
-    let sys = {
-        let mut s = sysinfo::System::new_all();
-        s.refresh_all();
-        s
-    };
+    let settings = ConfigHandler::load_settings().unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid settings: {}", e);
+        Default::default()
+    });
+    let metrics_dir = ConfigHandler::get_metrics_dir()
+        .expect("Failed to get metrics directory");
+    let metrics = MetricsHistory::new(metrics_dir, settings.metrics)
+        .expect("Failed to initialize metrics history");
+
+    AppState::new(log_handler, metrics)
+}
+
+/// Run headless: supervise processes and serve the control socket.
+pub fn run_daemon() -> Result<(), String> {
+    daemon::serve(init_state())
+}
 
-    let app_state = AppState {
-        manager: Arc::clone(&process_manager),
-        log_handler: Arc::clone(&log_handler),
-        system: Arc::new(std::sync::Mutex::new(sys)),
-        terminal: Arc::new(std::sync::Mutex::new(terminal::TerminalManager::new())),
-    };
+/// Run a `pm <command>` CLI invocation; returns the exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    cli::run(args)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let mut app_state = init_state();
+    // Processes outlive the window when a daemon supervises them.
+    app_state.daemon = daemon::connect_or_spawn().map(Arc::new);
+    let daemon_client = app_state.daemon.clone();
 
     tauri::Builder::default()
         .manage(app_state)
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .plugin(tauri_plugin_notification::init())
+        .setup(move |app| {
+            // Trigger `notify` actions, whether fired here or in the daemon.
+            let handle = app.handle().clone();
+            app.listen_any("process:notification", move |event| {
+                let Ok(payload) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+                    return;
+                };
+                let field = |key: &str| payload.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                let _ = handle
+                    .notification()
+                    .builder()
+                    .title(field("title"))
+                    .body(field("body"))
+                    .show();
+            });
+
+            // Without a daemon the processes run here, so they are sampled
+            // and their config files watched here.
+            if daemon_client.is_none() {
+                if let Some(window) = app.get_webview_window("main") {
+                    service::start_metrics_sampler(&app.state::<AppState>(), Arc::new(window.clone()));
+                    service::start_config_watcher(&app.state::<AppState>(), Arc::new(window));
+                }
+            }
+
+            // Relay daemon events to the window as if they were emitted locally.
+            #[cfg(unix)]
+            if let Some(client) = daemon_client {
+                let handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    let _ = client.subscribe(|event, payload| {
+                        let _ = handle.emit(&event, payload);
+                    });
+                });
+            }
+
             // Build system tray menu
             let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
             let hide = MenuItem::with_id(app, "hide", "Hide", true, None::<&str>)?;
@@ -122,16 +184,30 @@ pub fn run() {
             commands::stop_process,
             commands::restart_process,
             commands::add_process,
+            commands::import_processes,
             commands::remove_process,
             commands::update_process,
             commands::get_metrics,
+            commands::get_metrics_history,
             commands::get_logs,
+            commands::query_logs,
+            commands::search_logs,
+            commands::export_logs,
+            commands::create_support_bundle,
             commands::clear_logs,
             commands::save_config,
             commands::load_config,
+            commands::check_config,
+            commands::list_config_backups,
+            commands::restore_config_backup,
             commands::set_auto_start,
             commands::start_all,
             commands::stop_all,
+            commands::open_workspace,
+            commands::close_workspace,
+            commands::list_workspaces,
+            commands::workspace_up,
+            commands::workspace_down,
             commands::terminal_run,
             commands::terminal_kill,
             commands::terminal_set_cwd,
@@ -1,105 +1,1199 @@
-use crate::types::LogEntry;
-use chrono::Local;
-use std::fs::{self, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use crate::redact::Redactor;
+use crate::types::{LogCursor, LogEntry, LogExportFormat, LogPage, LogPolicy, LogQuery};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// How often buffered log lines are flushed to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const MB: u64 = 1024 * 1024;
+
+/// Entries returned by a query that doesn't set `limit`.
+const DEFAULT_PAGE_SIZE: usize = 1000;
+/// Bytes read per step when scanning backwards.
+const REVERSE_CHUNK: u64 = 64 * 1024;
+
+/// Buffered handle on a process's active log file.
+struct LogWriter {
+    file: BufWriter<File>,
+    size: u64,
+    opened: SystemTime,
+}
+
+impl LogWriter {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // An existing file keeps aging from when it was first written.
+        let opened = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        Ok(LogWriter {
+            file: BufWriter::new(file),
+            size: metadata.len(),
+            opened,
+        })
+    }
+
+    /// Whether writing `incoming` more bytes should start a new segment.
+    fn due_for_rotation(&self, policy: &LogPolicy, incoming: u64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let too_big = policy.max_size_mb > 0 && self.size + incoming > policy.max_size_mb * MB;
+        let too_old = policy.max_age_hours > 0
+            && self
+                .opened
+                .elapsed()
+                .is_ok_and(|age| age >= Duration::from_secs(policy.max_age_hours * 3600));
+        too_big || too_old
+    }
+}
+
+#[derive(Default)]
+struct ProcessLog {
+    policy: LogPolicy,
+    redactor: Redactor,
+    writer: Option<LogWriter>,
+    /// Run the sequence counter belongs to.
+    run_id: u64,
+    seq: u64,
+}
 
 pub struct LogHandler {
     log_dir: PathBuf,
+    logs: Mutex<HashMap<String, ProcessLog>>,
 }
 
 impl LogHandler {
     pub fn new(log_dir: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
         fs::create_dir_all(&log_dir)?;
-        Ok(LogHandler { log_dir })
+        Ok(LogHandler {
+            log_dir,
+            logs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Flush buffered lines in the background so quiet processes still
+    /// reach disk promptly. Stops once the handler is dropped.
+    pub fn start_flusher(handler: &Arc<LogHandler>) {
+        let handler = Arc::downgrade(handler);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            match handler.upgrade() {
+                Some(handler) => handler.flush_all(),
+                None => break,
+            }
+        });
     }
 
     pub fn get_log_file(&self, process_id: &str) -> PathBuf {
         self.log_dir.join(format!("{}.log", process_id))
     }
 
-    pub fn write_log(&self, process_id: &str, level: &str, message: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let log_path = self.get_log_file(process_id);
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_path)?;
+    /// Set the rotation and retention limits for a process and prune its
+    /// rotated segments against them.
+    pub fn set_policy(&self, process_id: &str, policy: LogPolicy) {
+        if let Ok(mut logs) = self.logs.lock() {
+            logs.entry(process_id.to_string()).or_default().policy = policy.clone();
+        }
+        apply_retention(&self.log_dir, process_id, &policy);
+    }
+
+    /// Set what is masked out of a process's lines from now on.
+    pub fn set_redactor(&self, process_id: &str, redactor: Redactor) {
+        if let Ok(mut logs) = self.logs.lock() {
+            logs.entry(process_id.to_string()).or_default().redactor = redactor;
+        }
+    }
 
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-        let log_line = format!("[{}] [{}] {}\n", timestamp, level, message);
-        file.write_all(log_line.as_bytes())?;
+    /// Append a line to the process's log and return the stored entry,
+    /// with secrets already masked.
+    pub fn write_log(
+        &self,
+        process_id: &str,
+        run_id: u64,
+        stream: &str,
+        message: &str,
+    ) -> Result<LogEntry, Box<dyn std::error::Error>> {
+        let mut logs = self.logs.lock().map_err(|e| e.to_string())?;
+        let log = logs.entry(process_id.to_string()).or_default();
 
-        Ok(())
+        if log.run_id != run_id {
+            log.run_id = run_id;
+            log.seq = 0;
+        }
+        log.seq += 1;
+
+        let mut entry = LogEntry {
+            timestamp: format_time(Utc::now()),
+            stream: stream.to_string(),
+            message: log.redactor.redact(message).into_owned(),
+            run_id,
+            seq: log.seq,
+            level: None,
+            fields: None,
+            offset: 0,
+        };
+        if log.policy.parse_json {
+            index_json(&mut entry);
+        }
+        let mut log_line = serde_json::to_string(&Record::from(&entry))?;
+        log_line.push('\n');
+
+        if log
+            .writer
+            .as_ref()
+            .is_some_and(|w| w.due_for_rotation(&log.policy, log_line.len() as u64))
+        {
+            if let Some(mut writer) = log.writer.take() {
+                writer.file.flush()?;
+            }
+            self.rotate(process_id, &log.policy)?;
+        }
+
+        let writer = match &mut log.writer {
+            Some(writer) => writer,
+            None => log.writer.insert(LogWriter::open(&self.get_log_file(process_id))?),
+        };
+        entry.offset = writer.size;
+        writer.file.write_all(log_line.as_bytes())?;
+        writer.size += log_line.len() as u64;
+
+        Ok(entry)
     }
 
+    fn flush(&self, process_id: &str) {
+        if let Ok(mut logs) = self.logs.lock() {
+            if let Some(writer) = logs.get_mut(process_id).and_then(|l| l.writer.as_mut()) {
+                let _ = writer.file.flush();
+            }
+        }
+    }
+
+    pub fn flush_all(&self) {
+        if let Ok(mut logs) = self.logs.lock() {
+            for writer in logs.values_mut().filter_map(|l| l.writer.as_mut()) {
+                let _ = writer.file.flush();
+            }
+        }
+    }
+
+    /// The last `limit` entries.
     pub fn read_logs(&self, process_id: &str, limit: Option<usize>) -> Result<Vec<LogEntry>, Box<dyn std::error::Error>> {
-        let log_path = self.get_log_file(process_id);
-        
-        if !log_path.exists() {
-            return Ok(Vec::new());
+        let query = LogQuery {
+            limit: Some(limit.unwrap_or(usize::MAX)),
+            ..LogQuery::default()
+        };
+        Ok(self.query_logs(process_id, &query)?.entries)
+    }
+
+    /// Fetch a page of entries without reading whole files: pages are read
+    /// outwards from a cursor, through rotated segments and then the active
+    /// log, and time bounds are found by bisection. A compressed segment is
+    /// inflated in memory once a page reaches it.
+    pub fn query_logs(&self, process_id: &str, query: &LogQuery) -> Result<LogPage, Box<dyn std::error::Error>> {
+        self.flush(process_id);
+
+        let since = query.since.as_deref().map(normalize_time).transpose()?;
+        let until = query.until.as_deref().map(normalize_time).transpose()?;
+        let filter = Filter::new(query)?;
+        let matches = |entry: &LogEntry| filter.matches(entry);
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+        // Each file ends where the next one begins, so the time bounds
+        // narrow the files down before any is opened.
+        let mut files = self.log_files(process_id);
+        if let Some(until) = &until {
+            files.retain(|file| file.key < *until);
+        }
+        if let Some(since) = &since {
+            let first = files.iter().rposition(|file| file.key <= *since).unwrap_or(0);
+            files.drain(..first);
         }
+        let Some(newest) = files.len().checked_sub(1) else {
+            return Ok(LogPage::default());
+        };
+        let cursor = |index: usize, offset: u64| LogCursor {
+            file: files[index].key.clone(),
+            offset,
+        };
+        // A file with the bounds of the queried range within it.
+        let open = |index: usize| -> std::io::Result<(Box<dyn LogSource>, u64, u64)> {
+            let mut source = open_seekable(&files[index].path)?;
+            let len = source.seek(SeekFrom::End(0))?;
+            let start = match &since {
+                Some(since) if index == 0 => offset_for_time(&mut source, len, since)?,
+                _ => 0,
+            };
+            let end = match &until {
+                Some(until) if index == newest => offset_for_time(&mut source, len, until)?,
+                _ => len,
+            };
+            Ok((source, start, end))
+        };
 
-        let content = std::fs::read_to_string(&log_path)?;
-        let mut logs: Vec<LogEntry> = content
-            .lines()
-            .filter_map(|line| {
-                if line.is_empty() {
-                    return None;
+        let mut page = LogPage::default();
+        if let Some(after) = &query.after {
+            // The cursor's file is gone once pruned; carry on with the next.
+            let Some(mut index) = files.iter().position(|file| file.key >= after.file) else {
+                page.after = after.clone();
+                page.before = Some(after.clone());
+                return Ok(page);
+            };
+            let (mut source, start, mut end) = open(index)?;
+            let mut from = if files[index].key == after.file {
+                after.offset.clamp(start, end)
+            } else {
+                start
+            };
+            page.before = (index > 0 || from > start).then(|| cursor(index, from));
+            loop {
+                let pos = read_forward(&mut source, from, end, &matches, limit, &mut page.entries)?;
+                page.after = cursor(index, pos);
+                if page.entries.len() >= limit || index == newest {
+                    break;
                 }
-                
-                // Parse format: [timestamp] [level] message
-                if let Some(timestamp_end) = line.find(']') {
-                    if let Some(level_end) = line[timestamp_end + 1..].find(']') {
-                        let timestamp = line[1..timestamp_end].to_string();
-                        let level = line[timestamp_end + 2..timestamp_end + 2 + level_end].trim().to_string();
-                        let message = line[timestamp_end + 3 + level_end..].trim().to_string();
-                        
-                        return Some(LogEntry {
-                            timestamp,
-                            level,
-                            message,
-                        });
+                index += 1;
+                (source, from, end) = open(index)?;
+            }
+        } else {
+            let (mut index, mut to) = match &query.before {
+                None => (newest, None),
+                Some(before) => match files.iter().position(|file| file.key == before.file) {
+                    Some(index) => (index, Some(before.offset)),
+                    // Newer than the range: page back from its end.
+                    None if files[newest].key < before.file => (newest, None),
+                    // Pruned, or older than the range: nothing is left.
+                    None => {
+                        page.after = before.clone();
+                        return Ok(page);
+                    }
+                },
+            };
+            let mut after = None;
+            loop {
+                let (source, start, end) = open(index)?;
+                let to = to.take().map_or(end, |to| to.clamp(start, end));
+                after.get_or_insert_with(|| cursor(index, to));
+                match read_backward(source, start, to, &matches, limit, &mut page.entries)? {
+                    Some(offset) => {
+                        page.before = Some(cursor(index, offset));
+                        break;
                     }
+                    None if index == 0 => break,
+                    None if page.entries.len() >= limit => {
+                        page.before = Some(cursor(index, start));
+                        break;
+                    }
+                    None => index -= 1,
                 }
-                None
+            }
+            page.entries.reverse();
+            page.after = after.unwrap_or_default();
+        }
+
+        Ok(page)
+    }
+
+    /// The rotated segments and the active log that hold entries, oldest
+    /// first.
+    fn log_files(&self, process_id: &str) -> Vec<LogFile> {
+        let mut paths: Vec<PathBuf> = rotated_segments(&self.log_dir, process_id)
+            .into_iter()
+            .rev()
+            .map(|segment| segment.path)
+            .collect();
+        paths.push(self.get_log_file(process_id));
+        let mut files: Vec<LogFile> = paths
+            .into_iter()
+            .filter_map(|path| {
+                let key = first_timestamp(&path)?;
+                Some(LogFile { path, key })
             })
             .collect();
+        files.sort_by(|a, b| a.key.cmp(&b.key));
+        files
+    }
 
-        // Apply limit (last N entries)
-        if let Some(limit_val) = limit {
-            if logs.len() > limit_val {
-                let start = logs.len() - limit_val;
-                logs = logs[start..].to_vec();
+    /// Write every matching entry to `out`, oldest first, reading rotated
+    /// segments before the active log. Cursors and `limit` are ignored.
+    /// Returns the number of entries written.
+    pub fn export_logs(
+        &self,
+        process_id: &str,
+        query: &LogQuery,
+        format: LogExportFormat,
+        out: &mut dyn Write,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        self.flush(process_id);
+
+        let filter = Filter::new(query)?;
+        let since = query.since.as_deref().map(normalize_time).transpose()?;
+        let until = query.until.as_deref().map(normalize_time).transpose()?;
+        // A segment's mtime is its last write, so older ones hold nothing newer.
+        let since_time = since
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(SystemTime::from);
+
+        let mut paths: Vec<PathBuf> = rotated_segments(&self.log_dir, process_id)
+            .into_iter()
+            .rev()
+            .filter(|segment| since_time.is_none_or(|t| segment.modified >= t))
+            .map(|segment| segment.path)
+            .collect();
+        paths.push(self.get_log_file(process_id));
+
+        if format == LogExportFormat::Csv {
+            writeln!(out, "timestamp,stream,run,seq,level,message")?;
+        }
+        let mut written = 0;
+        for path in paths {
+            let Ok(reader) = open_reader(&path) else { continue };
+            let mut past_end = false;
+            for_each_entry(reader, |entry| {
+                if until.as_ref().is_some_and(|until| entry.timestamp >= *until) {
+                    past_end = true;
+                    return Ok(false);
+                }
+                if since.as_ref().is_none_or(|since| entry.timestamp >= *since) && filter.matches(&entry) {
+                    write_entry(out, &entry, format)?;
+                    written += 1;
+                }
+                Ok(true)
+            })?;
+            if past_end {
+                break;
             }
         }
-
-        Ok(logs)
+        out.flush()?;
+        Ok(written)
     }
 
+    /// Delete the active log and every rotated segment.
     pub fn clear_logs(&self, process_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Ok(mut logs) = self.logs.lock() {
+            if let Some(log) = logs.get_mut(process_id) {
+                log.writer = None;
+            }
+        }
         let log_path = self.get_log_file(process_id);
         if log_path.exists() {
             fs::remove_file(log_path)?;
         }
+        for segment in rotated_segments(&self.log_dir, process_id) {
+            fs::remove_file(segment.path)?;
+        }
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn rotate_log(&self, process_id: &str, max_size_mb: u64) -> Result<(), Box<dyn std::error::Error>> {
+    /// Move the active log aside as `<id>.<timestamp>.log`, then compress
+    /// and prune segments in the background.
+    fn rotate(&self, process_id: &str, policy: &LogPolicy) -> Result<(), Box<dyn std::error::Error>> {
         let log_path = self.get_log_file(process_id);
-        
-        if log_path.exists() {
-            let metadata = std::fs::metadata(&log_path)?;
-            let size_mb = metadata.len() / (1024 * 1024);
-            
-            if size_mb > max_size_mb {
-                let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-                let backup_name = format!("{}.{}.log", process_id, timestamp);
-                let backup_path = self.log_dir.join(backup_name);
-                fs::rename(&log_path, &backup_path)?;
-            }
+        if !log_path.exists() {
+            return Ok(());
+        }
+
+        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+        let mut backup_path = self.log_dir.join(format!("{}.{}.log", process_id, timestamp));
+        let mut n = 1;
+        // Several rotations can land in the same second.
+        while backup_path.exists() || gz_path(&backup_path).exists() {
+            backup_path = self.log_dir.join(format!("{}.{}-{}.log", process_id, timestamp, n));
+            n += 1;
         }
+        fs::rename(&log_path, &backup_path)?;
+
+        let log_dir = self.log_dir.clone();
+        let process_id = process_id.to_string();
+        let policy = policy.clone();
+        std::thread::spawn(move || {
+            if policy.compress {
+                let _ = compress(&backup_path);
+            }
+            apply_retention(&log_dir, &process_id, &policy);
+        });
 
         Ok(())
     }
 }
+
+impl Drop for LogHandler {
+    fn drop(&mut self) {
+        self.flush_all();
+    }
+}
+
+/// On-disk form of a [`LogEntry`]: one JSON object per line.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    ts: String,
+    stream: String,
+    run: u64,
+    seq: u64,
+    msg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fields: Option<Map<String, Value>>,
+}
+
+impl From<&LogEntry> for Record {
+    fn from(entry: &LogEntry) -> Self {
+        Record {
+            ts: entry.timestamp.clone(),
+            stream: entry.stream.clone(),
+            run: entry.run_id,
+            seq: entry.seq,
+            msg: entry.message.clone(),
+            level: entry.level.clone(),
+            fields: entry.fields.clone(),
+        }
+    }
+}
+
+/// A log file holding entries, known by the timestamp of its first one.
+/// Unlike its path, that survives rotation and compression.
+struct LogFile {
+    path: PathBuf,
+    key: String,
+}
+
+trait LogSource: Read + Seek {}
+
+impl<T: Read + Seek> LogSource for T {}
+
+/// Open a log file to read from the start. A segment compressed since it
+/// was listed is found under its `.gz` name.
+fn open_reader(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        return Ok(Box::new(BufReader::new(GzDecoder::new(File::open(path)?))));
+    }
+    match File::open(path) {
+        Ok(file) => Ok(Box::new(BufReader::new(file))),
+        Err(e) if e.kind() == ErrorKind::NotFound && gz_path(path).exists() => open_reader(&gz_path(path)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Open a log file for seeking; compressed segments are inflated in memory.
+fn open_seekable(path: &Path) -> std::io::Result<Box<dyn LogSource>> {
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let mut data = Vec::new();
+        GzDecoder::new(File::open(path)?).read_to_end(&mut data)?;
+        return Ok(Box::new(std::io::Cursor::new(data)));
+    }
+    match File::open(path) {
+        Ok(file) => Ok(Box::new(file)),
+        Err(e) if e.kind() == ErrorKind::NotFound && gz_path(path).exists() => open_seekable(&gz_path(path)),
+        Err(e) => Err(e),
+    }
+}
+
+fn first_timestamp(path: &Path) -> Option<String> {
+    let mut reader = open_reader(path).ok()?;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).ok()? == 0 {
+            return None;
+        }
+        if let Line::Entry(entry) = parse_line(&line, 0) {
+            return Some(entry.timestamp);
+        }
+    }
+}
+
+/// Append the entries of `[from, end)` that `matches` accepts until
+/// `entries` holds `limit`, and return where reading stopped. An entry is
+/// complete once the next one starts, since legacy entries may continue
+/// over several lines.
+fn read_forward(
+    source: &mut dyn LogSource,
+    from: u64,
+    end: u64,
+    matches: &dyn Fn(&LogEntry) -> bool,
+    limit: usize,
+    entries: &mut Vec<LogEntry>,
+) -> std::io::Result<u64> {
+    let mut reader = BufReader::new(source);
+    reader.seek(SeekFrom::Start(from))?;
+
+    let mut pos = from;
+    let mut current: Option<LogEntry> = None;
+    let mut line = Vec::new();
+    while pos < end {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        match parse_line(&line, pos) {
+            Line::Entry(entry) => {
+                if let Some(done) = current.take().filter(|e| matches(e)) {
+                    entries.push(done);
+                    if entries.len() >= limit {
+                        break;
+                    }
+                }
+                current = Some(entry);
+            }
+            Line::Continuation(text) => {
+                if let Some(entry) = current.as_mut().filter(|e| e.run_id == 0) {
+                    entry.message.push('\n');
+                    entry.message.push_str(&text);
+                }
+            }
+            Line::Blank => {}
+        }
+        pos += read;
+    }
+    if let Some(done) = current.filter(|e| matches(e)) {
+        entries.push(done);
+    }
+    Ok(pos)
+}
+
+/// Push the entries of `[start, to)` that `matches` accepts, newest first,
+/// until `entries` holds `limit`. Returns the offset of the last entry read
+/// when stopping early with more before it.
+fn read_backward(
+    source: Box<dyn LogSource>,
+    start: u64,
+    to: u64,
+    matches: &dyn Fn(&LogEntry) -> bool,
+    limit: usize,
+    entries: &mut Vec<LogEntry>,
+) -> std::io::Result<Option<u64>> {
+    let mut lines = ReverseLines::new(source, start, to);
+    // Continuation lines are met before the entry they belong to.
+    let mut continuation: Vec<String> = Vec::new();
+    let mut earliest = None;
+    while entries.len() < limit {
+        let Some((offset, line)) = lines.next_line()? else {
+            return Ok(None);
+        };
+        match parse_line(&line, offset) {
+            Line::Entry(mut entry) => {
+                if entry.run_id == 0 {
+                    for text in continuation.iter().rev() {
+                        entry.message.push('\n');
+                        entry.message.push_str(text);
+                    }
+                }
+                continuation.clear();
+                earliest = Some(offset);
+                if matches(&entry) {
+                    entries.push(entry);
+                }
+            }
+            Line::Continuation(text) => continuation.push(text),
+            Line::Blank => {}
+        }
+    }
+    Ok(earliest.filter(|&offset| offset > start))
+}
+
+enum Line {
+    Entry(LogEntry),
+    /// A line that doesn't start an entry: the rest of a multi-line
+    /// message in the legacy format.
+    Continuation(String),
+    Blank,
+}
+
+/// The stream, level and search conditions of a [`LogQuery`].
+struct Filter<'a> {
+    query: &'a LogQuery,
+    pattern: Option<Regex>,
+}
+
+impl<'a> Filter<'a> {
+    fn new(query: &'a LogQuery) -> Result<Self, String> {
+        let pattern = match &query.search {
+            Some(search) if !search.is_empty() => {
+                let source = if query.regex { search.clone() } else { regex::escape(search) };
+                let source = if query.ignore_case { format!("(?i){}", source) } else { source };
+                Some(Regex::new(&source).map_err(|e| format!("Invalid search pattern: {}", e))?)
+            }
+            _ => None,
+        };
+        Ok(Filter { query, pattern })
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let query = self.query;
+        (query.streams.is_empty() || query.streams.contains(&entry.stream))
+            && (query.levels.is_empty()
+                || entry
+                    .level
+                    .as_ref()
+                    .is_some_and(|level| query.levels.iter().any(|l| l.eq_ignore_ascii_case(level))))
+            && self.pattern.as_ref().is_none_or(|p| p.is_match(&entry.message))
+    }
+}
+
+/// Parse a JSONL record, or a legacy `[timestamp] [level] message` line.
+fn parse_line(line: &[u8], offset: u64) -> Line {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.is_empty() {
+        return Line::Blank;
+    }
+
+    if line.starts_with('{') {
+        if let Ok(record) = serde_json::from_str::<Record>(line) {
+            return Line::Entry(LogEntry {
+                timestamp: record.ts,
+                stream: record.stream,
+                message: record.msg,
+                run_id: record.run,
+                seq: record.seq,
+                level: record.level,
+                fields: record.fields,
+                offset,
+            });
+        }
+    }
+
+    match parse_legacy(line, offset) {
+        Some(entry) => Line::Entry(entry),
+        None => Line::Continuation(line.to_string()),
+    }
+}
+
+fn parse_legacy(line: &str, offset: u64) -> Option<LogEntry> {
+    let rest = line.strip_prefix('[')?;
+    let (timestamp, rest) = rest.split_once("] [")?;
+    let (stream, message) = rest.split_once(']')?;
+    // Legacy timestamps are local time without an offset.
+    let local = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.3f").ok()?;
+    let timestamp = Local.from_local_datetime(&local).earliest()?.with_timezone(&Utc);
+    Some(LogEntry {
+        timestamp: format_time(timestamp),
+        stream: stream.trim().to_string(),
+        message: message.trim().to_string(),
+        run_id: 0,
+        seq: 0,
+        level: None,
+        fields: None,
+        offset,
+    })
+}
+
+/// Call `f` with each entry read from `reader` until it returns false,
+/// joining legacy continuation lines onto their entry.
+fn for_each_entry(
+    mut reader: impl BufRead,
+    mut f: impl FnMut(LogEntry) -> std::io::Result<bool>,
+) -> std::io::Result<()> {
+    let mut pos = 0;
+    let mut current: Option<LogEntry> = None;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        match parse_line(&line, pos) {
+            Line::Entry(entry) => {
+                if let Some(done) = current.replace(entry) {
+                    if !f(done)? {
+                        return Ok(());
+                    }
+                }
+            }
+            Line::Continuation(text) => {
+                if let Some(entry) = current.as_mut().filter(|e| e.run_id == 0) {
+                    entry.message.push('\n');
+                    entry.message.push_str(&text);
+                }
+            }
+            Line::Blank => {}
+        }
+        pos += read;
+    }
+    if let Some(done) = current {
+        f(done)?;
+    }
+    Ok(())
+}
+
+fn write_entry(out: &mut dyn Write, entry: &LogEntry, format: LogExportFormat) -> std::io::Result<()> {
+    match format {
+        LogExportFormat::Text => writeln!(out, "{} [{}] {}", entry.timestamp, entry.stream, entry.message),
+        LogExportFormat::Jsonl => {
+            serde_json::to_writer(&mut *out, &Record::from(entry))?;
+            writeln!(out)
+        }
+        LogExportFormat::Csv => writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            entry.timestamp,
+            entry.stream,
+            entry.run_id,
+            entry.seq,
+            csv_field(entry.level.as_deref().unwrap_or_default()),
+            csv_field(&entry.message)
+        ),
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Fixed-width UTC timestamps, so they compare correctly as strings.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Parse a query bound: RFC 3339, or a local date/time such as
+/// "2024-05-01 12:00" or "2024-05-01".
+fn normalize_time(time: &str) -> Result<String, String> {
+    let time = time.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(time) {
+        return Ok(format_time(parsed.with_timezone(&Utc)));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(time, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| format!("Invalid time '{}'", time))?;
+    let local = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("Invalid local time '{}'", time))?;
+    Ok(format_time(local.with_timezone(&Utc)))
+}
+
+/// Pick out the level, message and fields of a line that is a JSON object
+/// (bunyan/pino, logrus, structlog and similar).
+fn index_json(entry: &mut LogEntry) {
+    let trimmed = entry.message.trim();
+    if !trimmed.starts_with('{') {
+        return;
+    }
+    let Ok(fields) = serde_json::from_str::<Map<String, Value>>(trimmed) else {
+        return;
+    };
+
+    entry.level = ["level", "lvl", "severity", "log.level"]
+        .iter()
+        .find_map(|key| match fields.get(*key)? {
+            Value::String(level) => Some(level.to_lowercase()),
+            // pino/bunyan numeric levels
+            Value::Number(n) => Some(
+                match n.as_u64()? {
+                    0..=10 => "trace",
+                    11..=20 => "debug",
+                    21..=30 => "info",
+                    31..=40 => "warn",
+                    41..=50 => "error",
+                    _ => "fatal",
+                }
+                .to_string(),
+            ),
+            _ => None,
+        });
+    if let Some(Value::String(msg)) = ["msg", "message"].iter().find_map(|key| fields.get(*key)) {
+        entry.message = msg.clone();
+    }
+    entry.fields = Some(fields);
+}
+
+/// Offset of the first line at or after `pos`.
+fn line_start_at<R: Read + Seek>(file: &mut R, pos: u64, len: u64) -> std::io::Result<u64> {
+    if pos == 0 {
+        return Ok(0);
+    }
+    let mut reader = BufReader::new(&mut *file);
+    reader.seek(SeekFrom::Start(pos - 1))?;
+    let mut skipped = Vec::new();
+    let read = reader.read_until(b'\n', &mut skipped)? as u64;
+    Ok((pos - 1 + read).min(len))
+}
+
+/// Timestamp of the first parseable line at or after `pos`.
+fn timestamp_at<R: Read + Seek>(file: &mut R, pos: u64, len: u64) -> std::io::Result<Option<String>> {
+    let start = line_start_at(file, pos, len)?;
+    let mut reader = BufReader::new(&mut *file);
+    reader.seek(SeekFrom::Start(start))?;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if let Line::Entry(entry) = parse_line(&line, 0) {
+            return Ok(Some(entry.timestamp));
+        }
+    }
+}
+
+/// Offset of the first line stamped at or after `time`.
+fn offset_for_time<R: Read + Seek>(file: &mut R, len: u64, time: &str) -> std::io::Result<u64> {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        match timestamp_at(file, mid, len)? {
+            Some(timestamp) if timestamp.as_str() < time => lo = mid + 1,
+            _ => hi = mid,
+        }
+    }
+    line_start_at(file, lo, len)
+}
+
+/// Reads the lines of `[floor, end)` from last to first in fixed-size
+/// chunks, so only the requested page is ever held in memory.
+struct ReverseLines<R> {
+    file: R,
+    floor: u64,
+    /// Start of the bytes held in `buf`.
+    pos: u64,
+    /// Unconsumed bytes `[pos, pos + buf.len())`.
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read + Seek> ReverseLines<R> {
+    fn new(file: R, floor: u64, end: u64) -> Self {
+        ReverseLines {
+            file,
+            floor,
+            pos: end,
+            buf: Vec::new(),
+            done: end <= floor,
+        }
+    }
+
+    /// The previous line and its offset; the trailing newline is stripped.
+    fn next_line(&mut self) -> std::io::Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            if self.done {
+                return Ok(None);
+            }
+            // Ignore the newline that terminates the last line of the range.
+            let search_end = if self.buf.last() == Some(&b'\n') { self.buf.len() - 1 } else { self.buf.len() };
+            if let Some(i) = self.buf[..search_end].iter().rposition(|&b| b == b'\n') {
+                let line = self.buf.split_off(i + 1);
+                return Ok(Some((self.pos + i as u64 + 1, line)));
+            }
+            if self.pos == self.floor {
+                self.done = true;
+                let line = std::mem::take(&mut self.buf);
+                return Ok((!line.is_empty()).then_some((self.floor, line)));
+            }
+
+            let from = self.pos.saturating_sub(REVERSE_CHUNK).max(self.floor);
+            let mut chunk = vec![0; (self.pos - from) as usize];
+            self.file.seek(SeekFrom::Start(from))?;
+            self.file.read_exact(&mut chunk)?;
+            chunk.extend_from_slice(&self.buf);
+            self.buf = chunk;
+            self.pos = from;
+        }
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Rotated segments of a process, newest first.
+fn rotated_segments(log_dir: &Path, process_id: &str) -> Vec<Segment> {
+    let prefix = format!("{}.", process_id);
+    let active = format!("{}.log", process_id);
+    let Ok(entries) = fs::read_dir(log_dir) else {
+        return Vec::new();
+    };
+
+    let mut segments: Vec<Segment> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with(&prefix) && name != active && (name.ends_with(".log") || name.ends_with(".log.gz"))
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(Segment {
+                path: entry.path(),
+                modified: metadata.modified().ok()?,
+                size: metadata.len(),
+            })
+        })
+        .collect();
+    segments.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| b.path.cmp(&a.path)));
+    segments
+}
+
+/// Delete rotated segments beyond the policy's count, age and size limits.
+fn apply_retention(log_dir: &Path, process_id: &str, policy: &LogPolicy) {
+    let max_age = Duration::from_secs(policy.retain_days * 24 * 3600);
+    let mut total = 0;
+    for (index, segment) in rotated_segments(log_dir, process_id).into_iter().enumerate() {
+        total += segment.size;
+        let too_many = policy.max_files > 0 && index >= policy.max_files;
+        let too_old = policy.retain_days > 0 && segment.modified.elapsed().is_ok_and(|age| age > max_age);
+        let too_big = policy.max_total_mb > 0 && total > policy.max_total_mb * MB;
+        if too_many || too_old || too_big {
+            let _ = fs::remove_file(&segment.path);
+        }
+    }
+}
+
+/// Gzip a rotated segment to `<path>.gz` and remove the original.
+fn compress(path: &Path) -> std::io::Result<()> {
+    let target = gz_path(path);
+    let partial = target.with_extension("gz.partial");
+
+    let mut input = File::open(path)?;
+    let modified = input.metadata()?.modified()?;
+    let mut encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    // Keep the segment's place in the newest-first ordering.
+    encoder.finish()?.set_modified(modified)?;
+
+    fs::rename(&partial, &target)?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &[u8]) -> (PathBuf, File) {
+        let path = std::env::temp_dir().join(format!("pm-log-handler-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        let file = File::open(&path).unwrap();
+        (path, file)
+    }
+
+    fn reverse(file: File, floor: u64, end: u64) -> Vec<(u64, String)> {
+        let mut lines = ReverseLines::new(file, floor, end);
+        let mut out = Vec::new();
+        while let Some((offset, line)) = lines.next_line().unwrap() {
+            out.push((offset, String::from_utf8(line).unwrap().trim_end_matches('\n').to_string()));
+        }
+        out
+    }
+
+    fn record(ts: &str, msg: &str) -> String {
+        format!("{{\"ts\":\"{}\",\"stream\":\"stdout\",\"run\":1,\"seq\":0,\"msg\":\"{}\"}}\n", ts, msg)
+    }
+
+    #[test]
+    fn reverse_lines_read_last_to_first() {
+        let contents = b"one\ntwo\n\nfour\n";
+        let (path, file) = temp_file("reverse", contents);
+        let len = contents.len() as u64;
+        assert_eq!(
+            reverse(file, 0, len),
+            [(9, "four".to_string()), (8, "".into()), (4, "two".into()), (0, "one".into())]
+        );
+        // An unterminated last line, and a range that starts mid-file.
+        assert_eq!(reverse(File::open(&path).unwrap(), 4, len - 1), [(9, "four".to_string()), (8, "".into()), (4, "two".into())]);
+        assert!(reverse(File::open(&path).unwrap(), 4, 4).is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reverse_lines_span_chunks() {
+        let mut contents = String::new();
+        let mut expected = Vec::new();
+        for i in 0..10_000 {
+            expected.push((contents.len() as u64, format!("line {:05} {}", i, "x".repeat(i % 37))));
+            contents.push_str(&expected.last().unwrap().1);
+            contents.push('\n');
+        }
+        assert!(contents.len() as u64 > 2 * REVERSE_CHUNK);
+        let (path, file) = temp_file("chunks", contents.as_bytes());
+        expected.reverse();
+        assert_eq!(reverse(file, 0, contents.len() as u64), expected);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn offset_for_time_finds_first_entry_at_or_after() {
+        let stamps = [
+            "2024-05-01T10:00:00.000Z",
+            "2024-05-01T10:00:01.000Z",
+            "2024-05-01T10:00:01.000Z",
+            "2024-05-01T10:00:05.000Z",
+            "2024-05-01T11:00:00.000Z",
+        ];
+        let mut contents = String::new();
+        let mut offsets = Vec::new();
+        for (i, ts) in stamps.iter().enumerate() {
+            offsets.push(contents.len() as u64);
+            contents.push_str(&record(ts, &format!("entry {}", i)));
+            // Lines without a timestamp are skipped while bisecting.
+            if i == 1 {
+                contents.push_str("continued\n");
+            }
+        }
+        let len = contents.len() as u64;
+        let (path, mut file) = temp_file("bisect", contents.as_bytes());
+
+        let mut at = |time: &str| offset_for_time(&mut file, len, time).unwrap();
+        assert_eq!(at("2024-01-01T00:00:00.000Z"), 0);
+        assert_eq!(at("2024-05-01T10:00:00.000Z"), offsets[0]);
+        assert_eq!(at("2024-05-01T10:00:00.500Z"), offsets[1]);
+        assert_eq!(at("2024-05-01T10:00:01.000Z"), offsets[1]);
+        assert_eq!(at("2024-05-01T10:00:02.000Z"), offsets[3]);
+        assert_eq!(at("2024-05-01T10:59:59.999Z"), offsets[4]);
+        assert_eq!(at("2025-01-01T00:00:00.000Z"), len);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parses_legacy_lines_as_local_time() {
+        let Line::Entry(entry) = parse_line(b"[2024-05-01 12:30:00.250] [stderr] boom \r\n", 42) else {
+            panic!("legacy line not parsed");
+        };
+        let local = NaiveDateTime::parse_from_str("2024-05-01 12:30:00.250", "%Y-%m-%d %H:%M:%S%.3f").unwrap();
+        let expected = Local.from_local_datetime(&local).earliest().unwrap().with_timezone(&Utc);
+        assert_eq!(entry.timestamp, format_time(expected));
+        assert_eq!((entry.stream.as_str(), entry.message.as_str()), ("stderr", "boom"));
+        assert_eq!((entry.run_id, entry.seq, entry.offset), (0, 0, 42));
+
+        assert!(matches!(parse_line(b"\n", 0), Line::Blank));
+        assert!(matches!(parse_line(b"[not a time] [stdout] x", 0), Line::Continuation(_)));
+        assert!(matches!(parse_line(b"{\"not\": \"a record\"}", 0), Line::Continuation(_)));
+    }
+
+    #[test]
+    fn legacy_continuations_join_their_entry() {
+        let contents = format!(
+            "[2024-05-01 12:00:00.000] [stdout] Traceback:\n  at main\n\n[2024-05-01 12:00:01.000] [stdout] next\n{}  stray\n",
+            record("2024-05-01T12:00:02.000Z", "jsonl")
+        );
+        let mut entries = Vec::new();
+        for_each_entry(contents.as_bytes(), |entry| {
+            entries.push(entry);
+            Ok(true)
+        })
+        .unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["Traceback:\n  at main", "next", "jsonl"]);
+        let offsets: Vec<u64> = entries.iter().map(|e| e.offset).collect();
+        let next = contents.find("[2024-05-01 12:00:01").unwrap() as u64;
+        let jsonl = contents.find('{').unwrap() as u64;
+        assert_eq!(offsets, [0, next, jsonl]);
+    }
+
+    /// Close the active log and move it aside, as `write_log` does once
+    /// the policy says so.
+    fn force_rotation(handler: &LogHandler, id: &str) {
+        let policy = {
+            let mut logs = handler.logs.lock().unwrap();
+            let log = logs.get_mut(id).unwrap();
+            log.writer.take().unwrap().file.flush().unwrap();
+            log.policy.clone()
+        };
+        handler.rotate(id, &policy).unwrap();
+        // Keep the next file's first entry on a later millisecond.
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    fn messages(entries: &[LogEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.message.clone()).collect()
+    }
+
+    #[test]
+    fn pages_across_rotated_segments() {
+        let dir = std::env::temp_dir().join(format!("pm-log-pages-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let handler = LogHandler::new(dir.clone()).unwrap();
+        handler.set_policy("app", LogPolicy { compress: true, ..Default::default() });
+
+        let mut expected = Vec::new();
+        let mut write = |prefix: &str| {
+            for i in 0..25 {
+                let message = format!("{} {:02}", prefix, i);
+                handler.write_log("app", 1, "stdout", &message).unwrap();
+                expected.push(message);
+            }
+        };
+        write("a");
+        let tail = handler.query_logs("app", &LogQuery::default()).unwrap().after;
+
+        force_rotation(&handler, "app");
+        // Let the first segment be compressed so paging reads through gzip.
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !rotated_segments(&dir, "app").iter().any(|s| s.path.extension().is_some_and(|e| e == "gz")) {
+            assert!(std::time::Instant::now() < deadline, "segment never compressed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        write("b");
+        force_rotation(&handler, "app");
+        write("c");
+
+        let query = |before: Option<LogCursor>, after: Option<LogCursor>| {
+            handler
+                .query_logs("app", &LogQuery { before, after, limit: Some(10), ..Default::default() })
+                .unwrap()
+        };
+
+        // Backwards from the newest entry down to the first segment.
+        let mut page = query(None, None);
+        let mut backward = messages(&page.entries);
+        while let Some(before) = page.before.take() {
+            page = query(Some(before), None);
+            backward.splice(0..0, messages(&page.entries));
+        }
+        assert_eq!(backward, expected);
+
+        // Forwards from a cursor taken before either rotation.
+        let mut forward = Vec::new();
+        let mut after = tail;
+        loop {
+            let page = query(None, Some(after));
+            if page.entries.is_empty() {
+                break;
+            }
+            forward.extend(messages(&page.entries));
+            after = page.after;
+        }
+        assert_eq!(forward, expected[25..]);
+
+        // The default cursor starts at the oldest segment.
+        let page = query(None, Some(LogCursor::default()));
+        assert_eq!(messages(&page.entries), expected[..10]);
+
+        drop(handler);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
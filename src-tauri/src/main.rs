@@ -2,5 +2,15 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    pm_lib::run()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => pm_lib::run(),
+        Some("--daemon") => {
+            if let Err(e) = pm_lib::run_daemon() {
+                eprintln!("pm daemon: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(_) => std::process::exit(pm_lib::run_cli(&args)),
+    }
 }
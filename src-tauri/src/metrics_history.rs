@@ -0,0 +1,270 @@
+//! Metrics history: recent raw samples in memory, plus 1-minute and 1-hour
+//! rollups appended to `<id>.1m.jsonl` / `<id>.1h.jsonl` in the metrics
+//! directory and pruned to their retention.
+
+use crate::types::{MetricsResolution, MetricsSample, MetricsSettings};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MINUTE_MS: u64 = 60 * 1000;
+const HOUR_MS: u64 = 60 * MINUTE_MS;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// Running averages and peaks of the samples in one period.
+#[derive(Clone, Default)]
+struct Rollup {
+    start: u64,
+    count: u32,
+    cpu: f64,
+    cpu_max: f32,
+    memory: f64,
+    memory_max: u64,
+    memory_percent: f64,
+    threads: Option<u32>,
+    open_fds: Option<u32>,
+    disk_read: f64,
+    disk_write: f64,
+}
+
+impl Rollup {
+    fn new(start: u64) -> Self {
+        Rollup {
+            start,
+            ..Rollup::default()
+        }
+    }
+
+    fn add(&mut self, sample: &MetricsSample) {
+        self.count += 1;
+        self.cpu += sample.cpu_percent as f64;
+        self.cpu_max = self.cpu_max.max(sample.cpu_max);
+        self.memory += sample.memory_bytes as f64;
+        self.memory_max = self.memory_max.max(sample.memory_max_bytes);
+        self.memory_percent += sample.memory_percent as f64;
+        // Counts are reported at their peak.
+        self.threads = self.threads.max(sample.threads);
+        self.open_fds = self.open_fds.max(sample.open_fds);
+        self.disk_read += sample.disk_read_bytes_per_sec;
+        self.disk_write += sample.disk_write_bytes_per_sec;
+    }
+
+    fn finish(&self) -> MetricsSample {
+        let n = self.count.max(1) as f64;
+        MetricsSample {
+            ts: self.start,
+            cpu_percent: (self.cpu / n) as f32,
+            cpu_max: self.cpu_max,
+            memory_bytes: (self.memory / n) as u64,
+            memory_max_bytes: self.memory_max,
+            memory_percent: (self.memory_percent / n) as f32,
+            threads: self.threads,
+            open_fds: self.open_fds,
+            disk_read_bytes_per_sec: self.disk_read / n,
+            disk_write_bytes_per_sec: self.disk_write / n,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Series {
+    raw: VecDeque<MetricsSample>,
+    minute: Option<Rollup>,
+    hour: Option<Rollup>,
+}
+
+pub struct MetricsHistory {
+    dir: PathBuf,
+    settings: MetricsSettings,
+    series: Mutex<HashMap<String, Series>>,
+}
+
+impl MetricsHistory {
+    pub fn new(dir: PathBuf, settings: MetricsSettings) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&dir)?;
+        Ok(MetricsHistory {
+            dir,
+            settings,
+            series: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.settings.interval_ms.max(100))
+    }
+
+    fn rollup_file(&self, process_id: &str, suffix: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}.jsonl", process_id, suffix))
+    }
+
+    /// Add a raw sample, writing out the minute and hour it completes.
+    pub fn record(&self, process_id: &str, sample: MetricsSample) -> Result<(), Box<dyn std::error::Error>> {
+        let mut all = self.series.lock().map_err(|e| e.to_string())?;
+        let series = all.entry(process_id.to_string()).or_default();
+
+        let minute_start = sample.ts - sample.ts % MINUTE_MS;
+        if series.minute.as_ref().is_some_and(|m| m.start != minute_start) {
+            if let Some(done) = series.minute.take() {
+                let point = done.finish();
+                append(&self.rollup_file(process_id, "1m"), &point)?;
+
+                let hour_start = point.ts - point.ts % HOUR_MS;
+                if series.hour.as_ref().is_some_and(|h| h.start != hour_start) {
+                    if let Some(done) = series.hour.take() {
+                        append(&self.rollup_file(process_id, "1h"), &done.finish())?;
+                        self.prune(process_id, sample.ts);
+                    }
+                }
+                series.hour.get_or_insert_with(|| Rollup::new(hour_start)).add(&point);
+            }
+        }
+        series.minute.get_or_insert_with(|| Rollup::new(minute_start)).add(&sample);
+
+        series.raw.push_back(sample);
+        while series.raw.len() > self.settings.raw_samples.max(1) {
+            series.raw.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Drop the in-memory samples of a process that no longer exists. Rollup
+    /// files already written are kept.
+    pub fn forget(&self, process_id: &str) {
+        if let Ok(mut all) = self.series.lock() {
+            all.remove(process_id);
+        }
+    }
+
+    /// The most recent raw sample.
+    pub fn latest(&self, process_id: &str) -> Option<MetricsSample> {
+        let all = self.series.lock().ok()?;
+        all.get(process_id)?.raw.back().cloned()
+    }
+
+    /// Samples with `from <= ts < to` (ms since the epoch), oldest first.
+    /// Rollups include the period still in progress.
+    pub fn query(
+        &self,
+        process_id: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        resolution: MetricsResolution,
+    ) -> Result<Vec<MetricsSample>, Box<dyn std::error::Error>> {
+        let all = self.series.lock().map_err(|e| e.to_string())?;
+        let series = all.get(process_id);
+
+        let resolution = match (resolution, from) {
+            (MetricsResolution::Auto, None) => MetricsResolution::Raw,
+            (MetricsResolution::Auto, Some(from)) => {
+                let oldest_raw = series.and_then(|s| s.raw.front()).map(|s| s.ts);
+                if oldest_raw.is_some_and(|oldest| oldest <= from) {
+                    MetricsResolution::Raw
+                } else if now_ms().saturating_sub(from) <= self.settings.minute_retention_days * DAY_MS {
+                    MetricsResolution::Minute
+                } else {
+                    MetricsResolution::Hour
+                }
+            }
+            (other, _) => other,
+        };
+        let from = from.unwrap_or(0);
+        let to = to.unwrap_or(u64::MAX);
+        let in_range = |s: &MetricsSample| s.ts >= from && s.ts < to;
+
+        let (suffix, current) = match resolution {
+            MetricsResolution::Minute => ("1m", series.and_then(|s| s.minute.clone())),
+            MetricsResolution::Hour => {
+                // The minute in progress hasn't been added to its hour yet.
+                let current = series.and_then(|s| match (s.hour.clone(), &s.minute) {
+                    (hour, Some(minute)) => {
+                        let start = minute.start - minute.start % HOUR_MS;
+                        let mut hour = hour.filter(|h| h.start == start).unwrap_or_else(|| Rollup::new(start));
+                        hour.add(&minute.finish());
+                        Some(hour)
+                    }
+                    (hour, None) => hour,
+                });
+                ("1h", current)
+            }
+            _ => {
+                return Ok(series
+                    .map(|s| s.raw.iter().filter(|s| in_range(s)).cloned().collect())
+                    .unwrap_or_default());
+            }
+        };
+        let mut samples = read_rollups(&self.rollup_file(process_id, suffix), &in_range)?;
+        samples.extend(current.map(|r| r.finish()).filter(in_range));
+        Ok(samples)
+    }
+
+    /// Drop rollups older than their retention.
+    fn prune(&self, process_id: &str, now: u64) {
+        for (suffix, days) in [
+            ("1m", self.settings.minute_retention_days),
+            ("1h", self.settings.hour_retention_days),
+        ] {
+            if days > 0 {
+                let cutoff = now.saturating_sub(days * DAY_MS);
+                let _ = prune_file(&self.rollup_file(process_id, suffix), cutoff);
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn append(path: &Path, sample: &MetricsSample) -> Result<(), Box<dyn std::error::Error>> {
+    let mut line = serde_json::to_string(sample)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn read_rollups(
+    path: &Path,
+    keep: &dyn Fn(&MetricsSample) -> bool,
+) -> Result<Vec<MetricsSample>, Box<dyn std::error::Error>> {
+    let Ok(file) = File::open(path) else {
+        return Ok(Vec::new());
+    };
+    let mut samples = Vec::new();
+    for line in BufReader::new(file).lines() {
+        // A torn last line from a crash is skipped.
+        if let Ok(sample) = serde_json::from_str::<MetricsSample>(&line?) {
+            if keep(&sample) {
+                samples.push(sample);
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Rewrite `path` without samples older than `cutoff`, if it has any.
+fn prune_file(path: &Path, cutoff: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let samples = read_rollups(path, &|_| true)?;
+    if samples.first().is_none_or(|s| s.ts >= cutoff) {
+        return Ok(());
+    }
+    let partial = path.with_extension("jsonl.partial");
+    let mut out = std::io::BufWriter::new(File::create(&partial)?);
+    for sample in samples.iter().filter(|s| s.ts >= cutoff) {
+        serde_json::to_writer(&mut out, sample)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    drop(out);
+    fs::rename(&partial, path)?;
+    Ok(())
+}
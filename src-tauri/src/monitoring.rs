@@ -1,62 +1,241 @@
-use crate::types::ProcessMetrics;
-use sysinfo::{Pid, System};
+use crate::types::ProcessUsage;
+use std::collections::{HashMap, HashSet};
+use sysinfo::{Pid, ProcessRefreshKind, System, UpdateKind};
 
 pub struct Monitoring;
 
+/// Counters for one process as of the last refresh.
+pub struct ProcessReading {
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub memory_percent: f32,
+    /// Totals since the process started.
+    pub disk_read_bytes: u64,
+    pub disk_written_bytes: u64,
+    pub threads: Option<u32>,
+    pub open_fds: Option<u32>,
+}
+
+impl ProcessReading {
+    fn add(&mut self, other: &ProcessReading) {
+        let sum = |a: Option<u32>, b: Option<u32>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.cpu_percent += other.cpu_percent;
+        self.memory_bytes += other.memory_bytes;
+        self.memory_percent += other.memory_percent;
+        self.disk_read_bytes += other.disk_read_bytes;
+        self.disk_written_bytes += other.disk_written_bytes;
+        self.threads = sum(self.threads, other.threads);
+        self.open_fds = sum(self.open_fds, other.open_fds);
+    }
+}
+
+/// Thread and open file counts from /proc.
+#[cfg(target_os = "linux")]
+fn proc_counts(pid: u32) -> (Option<u32>, Option<u32>) {
+    let threads = std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Threads:"))
+                .and_then(|n| n.trim().parse().ok())
+        });
+    let open_fds = std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|fds| fds.count() as u32);
+    (threads, open_fds)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn proc_counts(_pid: u32) -> (Option<u32>, Option<u32>) {
+    (None, None)
+}
+
 impl Monitoring {
-    /// Call with a persistent `System` that was created at startup.
-    /// Each call refreshes it so the CPU delta is computed from the
-    /// previous refresh (accurate after the first measurement).
-    pub fn get_process_metrics(pid: u32, system: &mut System) -> Result<ProcessMetrics, String> {
-        system.refresh_all();
-
-        let total_memory = system.total_memory(); // bytes
-        let pid_sysinfo = Pid::from_u32(pid);
-
-        if let Some(process) = system.process(pid_sysinfo) {
-            let cpu_percent = process.cpu_usage();
-            // sysinfo 0.30 returns bytes
-            let memory_bytes = process.memory();
-            let memory_mb = (memory_bytes as f64 / (1024.0 * 1024.0)).ceil() as u64;
-            let memory_percent = if total_memory > 0 {
+    /// Read `pid` from a `system` refreshed by a [`TreeSampler`] (or fully).
+    /// `None` if the process is gone.
+    pub fn read_process(pid: u32, system: &System) -> Option<ProcessReading> {
+        let process = system.process(Pid::from_u32(pid))?;
+        let total_memory = system.total_memory();
+        let memory_bytes = process.memory();
+        let disk = process.disk_usage();
+        let (threads, open_fds) = proc_counts(pid);
+        Some(ProcessReading {
+            cpu_percent: process.cpu_usage(),
+            memory_bytes,
+            memory_percent: if total_memory > 0 {
                 (memory_bytes as f64 / total_memory as f64 * 100.0) as f32
             } else {
                 0.0
+            },
+            disk_read_bytes: disk.total_read_bytes,
+            disk_written_bytes: disk.total_written_bytes,
+            threads,
+            open_fds,
+        })
+    }
+
+    /// Read `root` with the `children` a [`TreeSampler`] found for it: the
+    /// totals over the whole tree, and each child's own usage. `None` if
+    /// the root is gone; children that exited since the scan are skipped.
+    pub fn read_tree(root: u32, children: &[u32], system: &System) -> Option<(ProcessReading, Vec<ProcessUsage>)> {
+        let mut total = Self::read_process(root, system)?;
+        let mut usage = Vec::new();
+        for &pid in children {
+            let (Some(process), Some(reading)) = (system.process(Pid::from_u32(pid)), Self::read_process(pid, system))
+            else {
+                continue;
             };
+            total.add(&reading);
+            usage.push(ProcessUsage {
+                pid,
+                parent_pid: process.parent().map(|parent| parent.as_u32()),
+                name: process.name().to_string(),
+                cmdline: process.cmd().join(" "),
+                cpu_percent: reading.cpu_percent,
+                memory_bytes: reading.memory_bytes,
+                memory_percent: reading.memory_percent,
+            });
+        }
+        Some((total, usage))
+    }
 
-            Ok(ProcessMetrics {
-                cpu_percent,
-                memory_mb,
-                memory_percent,
-            })
-        } else {
-            Err("Process not found".to_string())
+    /// Every live descendant of `root` as `(pid, start_time)`, found by
+    /// walking parent links. Take this snapshot before stopping the root:
+    /// orphans are re-parented once it exits. Scans a private process list
+    /// without CPU or memory, leaving the sampler's baselines alone.
+    pub fn descendant_pids(root: u32) -> Vec<(u32, u64)> {
+        descendants(root, &process_list())
+    }
+
+    /// Kill any process from a [`Self::descendant_pids`] snapshot that is
+    /// still alive. Returns the pids that survived the kill. Blocks while
+    /// waiting for them to exit.
+    pub fn kill_stragglers(snapshot: &[(u32, u64)]) -> Vec<u32> {
+        if snapshot.is_empty() {
+            return Vec::new();
+        }
+
+        // Matching the start time guards against pid reuse.
+        let alive = |system: &System| -> Vec<u32> {
+            snapshot
+                .iter()
+                .filter(|(pid, started)| {
+                    system
+                        .process(Pid::from_u32(*pid))
+                        .is_some_and(|p| p.start_time() == *started)
+                })
+                .map(|(pid, _)| *pid)
+                .collect()
+        };
+
+        let system = process_list();
+        let stragglers = alive(&system);
+        if stragglers.is_empty() {
+            return stragglers;
+        }
+        for pid in &stragglers {
+            if let Some(process) = system.process(Pid::from_u32(*pid)) {
+                process.kill();
+            }
         }
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        alive(&process_list())
     }
+}
 
-    #[allow(dead_code)]
-    pub fn get_all_processes_metrics(system: &mut System) -> Result<Vec<(u32, ProcessMetrics)>, String> {
-        system.refresh_all();
-        let total_memory = system.total_memory();
-        let mut metrics = Vec::new();
+/// Every process on the machine, refreshed without CPU, memory or disk.
+fn process_list() -> System {
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessRefreshKind::new());
+    system
+}
 
-        for (pid, process) in system.processes() {
-            let cpu_percent = process.cpu_usage();
-            let memory_bytes = process.memory();
-            let memory_mb = (memory_bytes as f64 / (1024.0 * 1024.0)).ceil() as u64;
-            let memory_percent = if total_memory > 0 {
-                (memory_bytes as f64 / total_memory as f64 * 100.0) as f32
-            } else {
-                0.0
-            };
+/// The process table is scanned for new children every this many ticks;
+/// in between, only pids already known are read.
+const RESCAN_EVERY: u32 = 5;
+
+/// Keeps a `System` current for the managed process trees only, rather than
+/// refreshing every process on the machine.
+#[derive(Default)]
+pub struct TreeSampler {
+    /// Descendants of each root as of the last scan.
+    trees: HashMap<u32, Vec<u32>>,
+    ticks: u32,
+    /// Process list for finding children, refreshed without CPU or memory.
+    /// Kept apart because any refresh of a process resets the times its
+    /// CPU usage is measured against.
+    scan: System,
+}
 
-            metrics.push((pid.as_u32(), ProcessMetrics {
-                cpu_percent,
-                memory_mb,
-                memory_percent,
-            }));
+impl TreeSampler {
+    /// Refresh memory and the counters of `roots` and their descendants,
+    /// ready for [`Monitoring::read_process`]. Children started since the
+    /// last scan are picked up by the next one.
+    pub fn refresh(&mut self, roots: &[u32], system: &mut System) {
+        let rescan = self.ticks.is_multiple_of(RESCAN_EVERY) || roots.iter().any(|root| !self.trees.contains_key(root));
+        self.ticks = self.ticks.wrapping_add(1);
+
+        if rescan {
+            self.scan.refresh_processes_specifics(ProcessRefreshKind::new());
+            self.trees = roots
+                .iter()
+                .map(|&root| {
+                    let mut pids: Vec<u32> = descendants(root, &self.scan).into_iter().map(|(pid, _)| pid).collect();
+                    pids.sort_unstable();
+                    (root, pids)
+                })
+                .collect();
+        } else {
+            self.trees.retain(|root, _| roots.contains(root));
         }
 
-        Ok(metrics)
+        // Only the tracked pids are refreshed, CPU included; anything else
+        // is dropped from `system`.
+        let pids: Vec<Pid> = self
+            .trees
+            .iter()
+            .flat_map(|(root, children)| std::iter::once(root).chain(children))
+            .map(|&pid| Pid::from_u32(pid))
+            .collect();
+        let counters = ProcessRefreshKind::new()
+            .with_cpu()
+            .with_memory()
+            .with_disk_usage()
+            .with_cmd(UpdateKind::OnlyIfNotSet);
+        system.refresh_pids_specifics(&pids, counters);
+        system.refresh_memory();
+    }
+
+    /// Descendants of `root` as of the last scan.
+    pub fn children(&self, root: u32) -> &[u32] {
+        self.trees.get(&root).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Descendants of `root` in `system`'s process list as `(pid, start_time)`,
+/// found by walking parent links.
+fn descendants(root: u32, system: &System) -> Vec<(u32, u64)> {
+    let mut tree: HashSet<Pid> = HashSet::from([Pid::from_u32(root)]);
+    let mut found = Vec::new();
+    loop {
+        let before = tree.len();
+        for (pid, process) in system.processes() {
+            if tree.contains(pid) {
+                continue;
+            }
+            if process.parent().is_some_and(|parent| tree.contains(&parent)) {
+                tree.insert(*pid);
+                found.push((pid.as_u32(), process.start_time()));
+            }
+        }
+        if tree.len() == before {
+            break;
+        }
     }
+    found
 }
@@ -0,0 +1,230 @@
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_8};
+use std::io::{ErrorKind, Read};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// Emit an unterminated line (a prompt, a progress bar) after this much quiet.
+const IDLE_FLUSH: Duration = Duration::from_millis(500);
+/// Split lines longer than this so one runaway line can't exhaust memory.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+const READ_CHUNK: usize = 8192;
+
+/// Resolve a WHATWG encoding label such as "utf-8", "windows-1252",
+/// "shift_jis" or "utf-16le". `None` means UTF-8.
+pub fn encoding_for(label: Option<&str>) -> Result<&'static Encoding, String> {
+    match label {
+        None => Ok(UTF_8),
+        Some(label) => Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("Unknown output encoding '{}'", label)),
+    }
+}
+
+enum Chunk {
+    Data(Vec<u8>),
+    End(Option<std::io::Error>),
+}
+
+/// Read a child's output on background threads and call `on_line` for each
+/// line. Output is decoded with `encoding` (invalid bytes become U+FFFD),
+/// `\r` rewrites the current line like a terminal does, and a partial line
+/// is emitted once the stream goes quiet. Reading only stops at end of
+/// stream; a read error is reported as a final line.
+pub fn spawn_line_reader<R, F>(source: R, encoding: &'static Encoding, mut on_line: F)
+where
+    R: Read + Send + 'static,
+    F: FnMut(String) + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut source = source;
+        let mut buf = vec![0u8; READ_CHUNK];
+        loop {
+            match source.read(&mut buf) {
+                Ok(0) => {
+                    let _ = tx.send(Chunk::End(None));
+                    break;
+                }
+                Ok(n) => {
+                    if tx.send(Chunk::Data(buf[..n].to_vec())).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let _ = tx.send(Chunk::End(Some(e)));
+                    break;
+                }
+            }
+        }
+    });
+
+    std::thread::spawn(move || {
+        let mut splitter = LineSplitter::new(encoding);
+        loop {
+            match rx.recv_timeout(IDLE_FLUSH) {
+                Ok(Chunk::Data(bytes)) => splitter.push(&bytes, false, &mut on_line),
+                Ok(Chunk::End(error)) => {
+                    splitter.push(&[], true, &mut on_line);
+                    splitter.finish(&mut on_line);
+                    if let Some(e) = error {
+                        on_line(format!("[pm] output capture stopped: {}", e));
+                    }
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => splitter.flush(&mut on_line),
+                Err(RecvTimeoutError::Disconnected) => {
+                    splitter.finish(&mut on_line);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+struct LineSplitter {
+    decoder: Decoder,
+    line: String,
+    /// Saw `\r`; a following `\n` ends the line, anything else rewrites it.
+    carriage_return: bool,
+}
+
+impl LineSplitter {
+    fn new(encoding: &'static Encoding) -> Self {
+        LineSplitter {
+            decoder: encoding.new_decoder(),
+            line: String::new(),
+            carriage_return: false,
+        }
+    }
+
+    /// Decode `bytes`, keeping incomplete multi-byte sequences for the next call.
+    fn decode(&mut self, mut bytes: &[u8], last: bool) -> String {
+        let mut text = String::new();
+        loop {
+            let needed = self
+                .decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len() * 3 + 16);
+            text.reserve(needed);
+            let (result, read, _) = self.decoder.decode_to_string(bytes, &mut text, last);
+            bytes = &bytes[read..];
+            if let CoderResult::InputEmpty = result {
+                return text;
+            }
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8], last: bool, on_line: &mut impl FnMut(String)) {
+        for c in self.decode(bytes, last).chars() {
+            if self.carriage_return {
+                self.carriage_return = false;
+                if c == '\n' {
+                    on_line(std::mem::take(&mut self.line));
+                    continue;
+                }
+                self.line.clear();
+            }
+            match c {
+                '\n' => on_line(std::mem::take(&mut self.line)),
+                '\r' => self.carriage_return = true,
+                _ => {
+                    self.line.push(c);
+                    if self.line.len() >= MAX_LINE_BYTES {
+                        on_line(std::mem::take(&mut self.line));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit whatever is buffered as a line of its own. A line ended by `\r`
+    /// is held back: the next character either ends it (`\n`) or rewrites it,
+    /// and emitting it now would repeat or split it.
+    fn flush(&mut self, on_line: &mut impl FnMut(String)) {
+        if !self.carriage_return && !self.line.is_empty() {
+            on_line(std::mem::take(&mut self.line));
+        }
+    }
+
+    /// At end of stream nothing will rewrite the line, so emit it regardless.
+    fn finish(&mut self, on_line: &mut impl FnMut(String)) {
+        self.carriage_return = false;
+        self.flush(on_line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `chunks` to a splitter, flushing where a chunk is `None` (the
+    /// stream went quiet), and return the emitted lines.
+    fn split(encoding: &'static Encoding, chunks: &[Option<&[u8]>]) -> Vec<String> {
+        let mut splitter = LineSplitter::new(encoding);
+        let mut lines = Vec::new();
+        let mut on_line = |line: String| lines.push(line);
+        for chunk in chunks {
+            match chunk {
+                Some(bytes) => splitter.push(bytes, false, &mut on_line),
+                None => splitter.flush(&mut on_line),
+            }
+        }
+        splitter.push(&[], true, &mut on_line);
+        splitter.finish(&mut on_line);
+        lines
+    }
+
+    #[test]
+    fn splits_on_lf_and_crlf() {
+        assert_eq!(split(UTF_8, &[Some(b"one\ntwo\r\n\nthree")]), ["one", "two", "", "three"]);
+        assert_eq!(split(UTF_8, &[Some(b"a\r"), Some(b"\nb\n")]), ["a", "b"]);
+    }
+
+    #[test]
+    fn carriage_return_rewrites_the_line() {
+        assert_eq!(split(UTF_8, &[Some(b"10%\r50%\r100%\ndone\n")]), ["100%", "done"]);
+        // Nothing rewrites the last line at end of stream.
+        assert_eq!(split(UTF_8, &[Some(b"10%\r50%\r")]), ["50%"]);
+    }
+
+    #[test]
+    fn idle_flush_emits_partial_lines_once() {
+        assert_eq!(
+            split(UTF_8, &[Some(b"Password: "), None, None, Some(b"ok\n")]),
+            ["Password: ", "ok"]
+        );
+    }
+
+    #[test]
+    fn idle_flush_holds_back_a_line_ended_by_cr() {
+        // A CRLF split by a quiet period is still one line.
+        assert_eq!(split(UTF_8, &[Some(b"line\r"), None, Some(b"\nnext\n")]), ["line", "next"]);
+        // A progress bar is only emitted once something other than `\r` ends it.
+        assert_eq!(
+            split(UTF_8, &[Some(b"10%\r"), None, Some(b"20%\r"), None, Some(b"30%\n")]),
+            ["30%"]
+        );
+    }
+
+    #[test]
+    fn decodes_across_chunks() {
+        let text = "héllo wörld\n".as_bytes();
+        let chunks: Vec<Option<&[u8]>> = text.chunks(1).map(Some).collect();
+        assert_eq!(split(UTF_8, &chunks), ["héllo wörld"]);
+
+        let utf16: Vec<u8> = "hi\r\nthere\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let encoding = encoding_for(Some("utf-16le")).unwrap();
+        assert_eq!(split(encoding, &[Some(&utf16[..3]), Some(&utf16[3..])]), ["hi", "there"]);
+
+        assert_eq!(split(UTF_8, &[Some(b"bad \xff byte\n")]), ["bad \u{fffd} byte"]);
+        assert!(encoding_for(Some("no-such-encoding")).is_err());
+    }
+
+    #[test]
+    fn splits_overlong_lines() {
+        let long = vec![b'x'; MAX_LINE_BYTES + 10];
+        let lines = split(UTF_8, &[Some(&long), Some(b"\n")]);
+        assert_eq!(lines.iter().map(String::len).collect::<Vec<_>>(), [MAX_LINE_BYTES, 10]);
+    }
+}
@@ -1,11 +1,15 @@
+use crate::env_loader;
 use crate::types::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+#[cfg(unix)]
+use std::os::unix::process::CommandExt as _;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
@@ -20,9 +24,135 @@ pub struct ProcessInstance {
     pub auto_restart: bool,
     pub auto_start: bool,
     pub working_dir: Option<String>,
+    pub env: HashMap<String, String>,
+    pub env_file: Option<String>,
+    pub clear_env: bool,
     pub start_time: Option<u64>,
     pub crash_count: u32,
     pub should_restart: Arc<AtomicBool>,
+    /// Incremented on every spawn so a supervisor can tell whether the
+    /// child it is watching is still the current one.
+    pub run_id: u64,
+    pub restart_policy: RestartPolicy,
+    pub stop_signal: StopSignal,
+    pub stop_timeout_ms: u64,
+    pub depends_on: Vec<String>,
+    pub readiness: Option<HealthCheck>,
+    pub liveness: Option<HealthCheck>,
+    pub restart_on_unhealthy: bool,
+    pub log_policy: LogPolicy,
+    pub output_encoding: Option<String>,
+    pub secret_env: Vec<String>,
+    pub triggers: Vec<LogTrigger>,
+    pub limits: ResourceLimits,
+    pub health: Option<HealthStatus>,
+    /// Consecutive automatic restarts since the last long-enough run.
+    pub restart_attempts: u32,
+    /// Timestamps (ms) of recent automatic restarts, for the retry window.
+    pub restart_history: VecDeque<u64>,
+    /// Most recent crashes, oldest first.
+    pub crash_history: VecDeque<CrashRecord>,
+    /// When each trigger, by index, last fired; kept across runs so a
+    /// restart trigger can't loop faster than its cooldown.
+    pub trigger_fired: HashMap<usize, Instant>,
+    /// Descendants of the current run and their usage, as of the last
+    /// metrics sample.
+    pub children: Vec<ProcessUsage>,
+    /// Workspace the process was declared in; such processes live in its
+    /// project file rather than `processes.json`.
+    pub workspace: Option<String>,
+}
+
+/// Crashes remembered per process.
+const MAX_CRASH_HISTORY: usize = 50;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// A stop signal has been delivered; the caller waits for exit and
+/// escalates with [`ProcessManager::stop_process`] after `timeout`.
+pub struct PendingStop {
+    pub run_id: u64,
+    pub timeout: Duration,
+}
+
+/// Signal the whole process group led by `pid`. Every managed process is
+/// spawned as a group leader, so this reaches its grandchildren too.
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: i32) -> Result<(), String> {
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    let rc = unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(format!("Failed to signal process group {}: {}", pid, std::io::Error::last_os_error()))
+    }
+}
+
+/// Set the hard limits and priorities in `limits` in the child between
+/// fork and exec; a failure there fails the spawn.
+#[cfg(target_os = "linux")]
+fn apply_spawn_limits(cmd: &mut Command, limits: &ResourceLimits) {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    let mb = |n: u64| n.saturating_mul(1024 * 1024);
+    let rlimits: Vec<_> = [
+        (libc::RLIMIT_AS, limits.max_address_space_mb.map(mb)),
+        (libc::RLIMIT_NOFILE, limits.max_open_files),
+        (libc::RLIMIT_CORE, limits.max_core_mb.map(mb)),
+    ]
+    .into_iter()
+    .filter_map(|(resource, value)| value.map(|value| (resource, value)))
+    .collect();
+    let nice = limits.nice;
+    let ioprio = limits.ionice.map(IoPriority::as_raw);
+    if rlimits.is_empty() && nice.is_none() && ioprio.is_none() {
+        return;
+    }
+
+    let check = |rc: libc::c_long| {
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    };
+    // SAFETY: the closure only makes async-signal-safe syscalls and
+    // doesn't allocate.
+    unsafe {
+        cmd.pre_exec(move || {
+            for &(resource, value) in &rlimits {
+                let limit = libc::rlimit { rlim_cur: value, rlim_max: value };
+                check(libc::setrlimit(resource, &limit) as libc::c_long)?;
+            }
+            if let Some(nice) = nice {
+                check(libc::setpriority(libc::PRIO_PROCESS, 0, nice) as libc::c_long)?;
+            }
+            if let Some(ioprio) = ioprio {
+                check(libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Result of polling a supervised child for exit.
+pub enum ExitCheck {
+    /// The child is still alive.
+    Running,
+    /// The child exited and has been reaped; state is already updated.
+    /// `restart_in` is set when the supervisor should respawn it.
+    Exited {
+        code: Option<i32>,
+        status: ProcessStatus,
+        crash_count: u32,
+        restart_in: Option<Duration>,
+    },
+    /// The process was removed, stopped or respawned by someone else.
+    Detached,
 }
 
 impl ProcessInstance {
@@ -38,9 +168,32 @@ impl ProcessInstance {
             auto_restart,
             auto_start,
             working_dir: None,
+            env: HashMap::new(),
+            env_file: None,
+            clear_env: false,
             start_time: None,
             crash_count: 0,
             should_restart: Arc::new(AtomicBool::new(false)),
+            run_id: 0,
+            restart_policy: RestartPolicy::default(),
+            stop_signal: StopSignal::default(),
+            stop_timeout_ms: default_stop_timeout_ms(),
+            depends_on: Vec::new(),
+            readiness: None,
+            liveness: None,
+            restart_on_unhealthy: false,
+            log_policy: LogPolicy::default(),
+            output_encoding: None,
+            secret_env: Vec::new(),
+            triggers: Vec::new(),
+            limits: ResourceLimits::default(),
+            health: None,
+            restart_attempts: 0,
+            restart_history: VecDeque::new(),
+            crash_history: VecDeque::new(),
+            trigger_fired: HashMap::new(),
+            children: Vec::new(),
+            workspace: None,
         }
     }
 
@@ -56,21 +209,77 @@ impl ProcessInstance {
             auto_restart: config.auto_restart,
             auto_start: config.auto_start,
             working_dir: config.working_dir.clone(),
+            env: config.env.clone().unwrap_or_default(),
+            env_file: config.env_file.clone(),
+            clear_env: config.clear_env,
             start_time: None,
             crash_count: 0,
             should_restart: Arc::new(AtomicBool::new(false)),
+            run_id: 0,
+            restart_policy: config.restart_policy.clone(),
+            stop_signal: config.stop_signal,
+            stop_timeout_ms: config.stop_timeout_ms,
+            depends_on: config.depends_on.clone(),
+            readiness: config.readiness.clone(),
+            liveness: config.liveness.clone(),
+            restart_on_unhealthy: config.restart_on_unhealthy,
+            log_policy: config.log_policy.clone(),
+            output_encoding: config.output_encoding.clone(),
+            secret_env: config.secret_env.clone(),
+            triggers: config.triggers.clone(),
+            limits: config.limits.clone(),
+            health: None,
+            restart_attempts: 0,
+            restart_history: VecDeque::new(),
+            crash_history: VecDeque::new(),
+            trigger_fired: HashMap::new(),
+            children: Vec::new(),
+            workspace: None,
         }
     }
 
+    /// Whether `config` changes what the child is spawned with, so a
+    /// running process has to restart to pick it up.
+    pub fn spawn_differs(&self, config: &ProcessConfig) -> bool {
+        let hard_limits = |l: &ResourceLimits| (l.max_address_space_mb, l.max_open_files, l.max_core_mb, l.nice, l.ionice);
+        self.command != config.command
+            || self.args != config.args
+            || self.working_dir != config.working_dir
+            || self.env != config.env.clone().unwrap_or_default()
+            || self.env_file != config.env_file
+            || self.clear_env != config.clear_env
+            || hard_limits(&self.limits) != hard_limits(&config.limits)
+    }
+
+    /// Take every configured field from `config`, keeping the run state.
+    /// A running process uses the new settings from its next start.
+    pub fn apply_config(&mut self, config: &ProcessConfig) {
+        self.name = config.name.clone();
+        self.command = config.command.clone();
+        self.args = config.args.clone();
+        self.auto_restart = config.auto_restart;
+        self.auto_start = config.auto_start;
+        self.working_dir = config.working_dir.clone();
+        self.env = config.env.clone().unwrap_or_default();
+        self.env_file = config.env_file.clone();
+        self.clear_env = config.clear_env;
+        self.restart_policy = config.restart_policy.clone();
+        self.stop_signal = config.stop_signal;
+        self.stop_timeout_ms = config.stop_timeout_ms;
+        self.depends_on = config.depends_on.clone();
+        self.readiness = config.readiness.clone();
+        self.liveness = config.liveness.clone();
+        self.restart_on_unhealthy = config.restart_on_unhealthy;
+        self.log_policy = config.log_policy.clone();
+        self.output_encoding = config.output_encoding.clone();
+        self.secret_env = config.secret_env.clone();
+        self.triggers = config.triggers.clone();
+        self.limits = config.limits.clone();
+    }
+
     pub fn get_uptime_ms(&self) -> u64 {
         match self.start_time {
-            Some(start) => {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                now.saturating_sub(start)
-            }
+            Some(start) => now_ms().saturating_sub(start),
             None => 0,
         }
     }
@@ -86,11 +295,30 @@ impl ProcessInstance {
             auto_restart: self.auto_restart,
             auto_start: self.auto_start,
             working_dir: self.working_dir.clone(),
+            env: self.env.clone(),
+            env_file: self.env_file.clone(),
+            clear_env: self.clear_env,
+            depends_on: self.depends_on.clone(),
+            health: self.health,
             uptime_ms: self.get_uptime_ms(),
             crash_count: self.crash_count,
+            workspace: self.workspace.clone(),
         }
     }
 
+    fn record_crash(&mut self, exit_code: Option<i32>, uptime_ms: u64, error: Option<String>) {
+        if self.crash_history.len() >= MAX_CRASH_HISTORY {
+            self.crash_history.pop_front();
+        }
+        self.crash_history.push_back(CrashRecord {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            run_id: self.run_id,
+            exit_code,
+            uptime_ms,
+            error,
+        });
+    }
+
     pub fn to_config(&self) -> ProcessConfig {
         ProcessConfig {
             id: self.id.clone(),
@@ -100,22 +328,51 @@ impl ProcessInstance {
             auto_restart: self.auto_restart,
             auto_start: self.auto_start,
             working_dir: self.working_dir.clone(),
-            env: None,
+            env: if self.env.is_empty() { None } else { Some(self.env.clone()) },
+            env_file: self.env_file.clone(),
+            clear_env: self.clear_env,
+            restart_policy: self.restart_policy.clone(),
+            stop_signal: self.stop_signal,
+            stop_timeout_ms: self.stop_timeout_ms,
+            depends_on: self.depends_on.clone(),
+            readiness: self.readiness.clone(),
+            liveness: self.liveness.clone(),
+            restart_on_unhealthy: self.restart_on_unhealthy,
+            log_policy: self.log_policy.clone(),
+            output_encoding: self.output_encoding.clone(),
+            secret_env: self.secret_env.clone(),
+            triggers: self.triggers.clone(),
+            limits: self.limits.clone(),
         }
     }
 }
 
 pub struct ProcessManager {
     pub processes: HashMap<String, ProcessInstance>,
+    /// Open workspaces by name, with their project files.
+    pub workspaces: BTreeMap<String, PathBuf>,
 }
 
 impl ProcessManager {
     pub fn new() -> Self {
         ProcessManager {
             processes: HashMap::new(),
+            workspaces: BTreeMap::new(),
         }
     }
 
+    /// Ids of the processes declared in `workspace`.
+    pub fn workspace_processes(&self, workspace: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .processes
+            .values()
+            .filter(|p| p.workspace.as_deref() == Some(workspace))
+            .map(|p| p.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
     pub fn add_process(&mut self, name: String, command: String, args: Vec<String>, working_dir: Option<String>, auto_restart: bool) -> String {
         let mut process = ProcessInstance::new(name, command, args, auto_restart, false);
         process.working_dir = working_dir;
@@ -132,32 +389,182 @@ impl ProcessManager {
         self.processes.get_mut(id)
     }
 
+    /// Remove a process and drop it from every other process's `depends_on`.
     pub fn remove_process(&mut self, id: &str) -> Option<ProcessInstance> {
-        self.processes.remove(id)
+        let removed = self.processes.remove(id)?;
+        for process in self.processes.values_mut() {
+            process.depends_on.retain(|dep| dep != &removed.id && dep != &removed.name);
+        }
+        Some(removed)
+    }
+
+    /// Resolve a dependency reference, which may be a process id or name.
+    pub fn resolve_ref(&self, reference: &str) -> Result<String, String> {
+        if self.processes.contains_key(reference) {
+            return Ok(reference.to_string());
+        }
+        let mut matches = self.processes.values().filter(|p| p.name == reference);
+        match (matches.next(), matches.next()) {
+            (Some(p), None) => Ok(p.id.clone()),
+            (Some(_), Some(_)) => Err(format!("Process name '{}' is ambiguous", reference)),
+            (None, _) => Err(format!("Unknown process '{}'", reference)),
+        }
+    }
+
+    /// Check that every reference in `deps` resolves to one process.
+    pub fn validate_dependencies(&self, process_name: &str, deps: &[String]) -> Result<(), String> {
+        for dep in deps {
+            self.resolve_ref(dep)
+                .map_err(|e| format!("Invalid dependency of '{}': {}", process_name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Dependency edges by id. A reference that doesn't resolve, such as
+    /// one to a process left out of a broken config file, is kept on the
+    /// process but not waited for.
+    fn dependency_graph(&self) -> HashMap<String, Vec<String>> {
+        self.processes
+            .values()
+            .map(|process| {
+                let deps = process
+                    .depends_on
+                    .iter()
+                    .filter_map(|dep| self.resolve_ref(dep).ok())
+                    .collect();
+                (process.id.clone(), deps)
+            })
+            .collect()
+    }
+
+    /// Group processes into start levels: each level only depends on
+    /// earlier ones. Fails on cycles.
+    pub fn dependency_levels(&self) -> Result<Vec<Vec<String>>, String> {
+        let graph = self.dependency_graph();
+        let name_of = |id: &str| self.processes.get(id).map(|p| p.name.clone()).unwrap_or_default();
+
+        let mut placed: HashSet<String> = HashSet::new();
+        let mut levels = Vec::new();
+        while placed.len() < graph.len() {
+            // BTreeSet keeps each level in a stable (name, id) order.
+            let ready: BTreeSet<(String, String)> = graph
+                .iter()
+                .filter(|(id, deps)| !placed.contains(*id) && deps.iter().all(|d| placed.contains(d)))
+                .map(|(id, _)| (name_of(id), id.clone()))
+                .collect();
+            if ready.is_empty() {
+                let cycle = Self::find_cycle(&graph, &placed)
+                    .iter()
+                    .map(|id| name_of(id))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                return Err(format!("Dependency cycle: {}", cycle));
+            }
+            let level: Vec<String> = ready.into_iter().map(|(_, id)| id).collect();
+            placed.extend(level.iter().cloned());
+            levels.push(level);
+        }
+        Ok(levels)
+    }
+
+    /// Follow unplaced dependencies until a node repeats; only called when
+    /// every unplaced node has an unplaced dependency, so one always does.
+    fn find_cycle(graph: &HashMap<String, Vec<String>>, placed: &HashSet<String>) -> Vec<String> {
+        let Some(start) = graph.keys().filter(|id| !placed.contains(*id)).min() else {
+            return Vec::new();
+        };
+        let mut path = vec![start.clone()];
+        loop {
+            let current = path.last().cloned().unwrap_or_default();
+            let next = graph
+                .get(&current)
+                .and_then(|deps| deps.iter().find(|d| !placed.contains(*d)));
+            let Some(next) = next else {
+                return path;
+            };
+            if let Some(pos) = path.iter().position(|id| id == next) {
+                let mut cycle = path.split_off(pos);
+                cycle.push(next.clone());
+                return cycle;
+            }
+            path.push(next.clone());
+        }
+    }
+
+    /// Transitive dependencies of `id` grouped into start levels,
+    /// excluding `id` itself.
+    pub fn dependency_levels_of(&self, id: &str) -> Result<Vec<Vec<String>>, String> {
+        let graph = self.dependency_graph();
+        let mut needed: HashSet<String> = HashSet::new();
+        let mut stack: Vec<String> = graph.get(id).cloned().unwrap_or_default();
+        while let Some(dep) = stack.pop() {
+            if needed.insert(dep.clone()) {
+                stack.extend(graph.get(&dep).cloned().unwrap_or_default());
+            }
+        }
+        Ok(self
+            .dependency_levels()?
+            .into_iter()
+            .map(|level| level.into_iter().filter(|p| needed.contains(p)).collect::<Vec<_>>())
+            .filter(|level| !level.is_empty())
+            .collect())
     }
 
     pub fn get_all_processes(&self) -> Vec<ProcessState> {
         self.processes.values().map(|p| p.to_state()).collect()
     }
 
-    pub fn spawn_process(&mut self, id: &str) -> Result<(u32, ChildStdout, ChildStderr), String> {
+    /// Manually start the process, resetting its crash and retry counters.
+    /// Returns its pid, run id and output pipes.
+    pub fn spawn_process(&mut self, id: &str) -> Result<(u32, u64, ChildStdout, ChildStderr), String> {
+        if let Some(process) = self.get_process_mut(id) {
+            if !matches!(process.status, ProcessStatus::Running | ProcessStatus::Stopping) {
+                process.crash_count = 0;
+                process.restart_attempts = 0;
+                process.restart_history.clear();
+            }
+        }
+        self.respawn_process(id)
+    }
+
+    /// Start the process without touching its crash history; used by
+    /// the supervisor for automatic restarts.
+    pub fn respawn_process(&mut self, id: &str) -> Result<(u32, u64, ChildStdout, ChildStderr), String> {
         let process = self
             .get_process_mut(id)
             .ok_or_else(|| "Process not found".to_string())?;
 
         // Don't spawn if already running
-        if matches!(process.status, ProcessStatus::Running) {
+        if matches!(process.status, ProcessStatus::Running | ProcessStatus::Stopping) {
             return Err("Process is already running".to_string());
         }
 
+        let env = env_loader::resolve_env(
+            &process.env,
+            process.env_file.as_deref(),
+            process.working_dir.as_deref(),
+        )?;
+
         let mut cmd = Command::new(&process.command);
         cmd.args(&process.args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+
+        if process.clear_env {
+            cmd.env_clear();
+        }
+        cmd.envs(env);
         
         // Hide console window on Windows
         #[cfg(target_os = "windows")]
         cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+
+        // Own process group so stop can signal everything the command spawns
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        #[cfg(target_os = "linux")]
+        apply_spawn_limits(&mut cmd, &process.limits);
         
         if let Some(ref dir) = process.working_dir {
             cmd.current_dir(dir);
@@ -171,26 +578,31 @@ impl ProcessManager {
                 let stderr = child.stderr.take()
                     .ok_or_else(|| "Failed to capture stderr".to_string())?;
                 process.pid = Some(pid);
+                process.children.clear();
                 process.status = ProcessStatus::Running;
+                process.health = if process.readiness.is_some() || process.liveness.is_some() {
+                    Some(HealthStatus::Starting)
+                } else {
+                    None
+                };
                 process.child = Some(child);
-                process.start_time = Some(
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis() as u64,
-                );
-                process.crash_count = 0;
+                process.start_time = Some(now_ms());
                 process.should_restart.store(true, Ordering::SeqCst);
-                Ok((pid, stdout, stderr))
+                process.run_id += 1;
+                Ok((pid, process.run_id, stdout, stderr))
             }
             Err(e) => {
+                let error = format!("Failed to spawn process: {}", e);
                 process.status = ProcessStatus::Crashed;
                 process.crash_count += 1;
-                Err(format!("Failed to spawn process: {}", e))
+                process.record_crash(None, 0, Some(error.clone()));
+                Err(error)
             }
         }
     }
 
+    /// Kill the process immediately and mark it stopped.
+    /// Prefer [`Self::begin_stop`] to give it a chance to shut down cleanly.
     pub fn stop_process(&mut self, id: &str) -> Result<(), String> {
         let process = self
             .get_process_mut(id)
@@ -199,18 +611,204 @@ impl ProcessManager {
         process.should_restart.store(false, Ordering::SeqCst);
 
         if let Some(mut child) = process.child.take() {
+            #[cfg(unix)]
+            let _ = signal_group(child.id(), libc::SIGKILL);
             let _ = child.kill();
             let _ = child.wait();
         }
 
         process.status = ProcessStatus::Stopped;
         process.pid = None;
+        process.start_time = None;
+        process.health = None;
         Ok(())
     }
 
-    pub fn restart_process(&mut self, id: &str) -> Result<(u32, ChildStdout, ChildStderr), String> {
+    /// Send the configured stop signal and mark the process `Stopping`.
+    /// Returns `None` when there was nothing to wait for (not running, or
+    /// no signal support on this platform, in which case it was killed).
+    pub fn begin_stop(&mut self, id: &str) -> Result<Option<PendingStop>, String> {
+        let process = self
+            .get_process_mut(id)
+            .ok_or_else(|| "Process not found".to_string())?;
+
+        process.should_restart.store(false, Ordering::SeqCst);
+
+        let pid = match (process.child.as_ref(), process.pid) {
+            (Some(_), Some(pid)) => pid,
+            _ => {
+                process.status = ProcessStatus::Stopped;
+                process.pid = None;
+                return Ok(None);
+            }
+        };
+
+        #[cfg(unix)]
+        {
+            if signal_group(pid, process.stop_signal.as_raw()).is_ok() {
+                process.status = ProcessStatus::Stopping;
+                return Ok(Some(PendingStop {
+                    run_id: process.run_id,
+                    timeout: Duration::from_millis(process.stop_timeout_ms),
+                }));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = pid;
+
         self.stop_process(id)?;
-        self.spawn_process(id)
+        Ok(None)
+    }
+
+    /// Reap the child if it exited after [`Self::begin_stop`].
+    /// Returns true once the process is no longer running.
+    pub fn try_finish_stop(&mut self, id: &str, run_id: u64) -> bool {
+        let process = match self.get_process_mut(id) {
+            Some(p) if p.run_id == run_id => p,
+            _ => return true,
+        };
+        let exited = match process.child.as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        };
+        if exited {
+            process.child = None;
+            process.status = ProcessStatus::Stopped;
+            process.pid = None;
+            process.start_time = None;
+            process.health = None;
+        }
+        exited
+    }
+
+    /// Non-blocking exit check for the child spawned as `run_id`.
+    /// Reaps the child if it has exited and records the outcome.
+    pub fn check_exit(&mut self, id: &str, run_id: u64) -> ExitCheck {
+        let process = match self.get_process_mut(id) {
+            Some(p) if p.run_id == run_id => p,
+            _ => return ExitCheck::Detached,
+        };
+
+        let exit_status = match process.child.as_mut() {
+            Some(child) => match child.try_wait() {
+                Ok(Some(status)) => status,
+                Ok(None) => return ExitCheck::Running,
+                Err(_) => return ExitCheck::Running,
+            },
+            None => return ExitCheck::Detached,
+        };
+
+        let uptime = process.get_uptime_ms();
+        let leader_pid = process.pid;
+        process.child = None;
+        process.pid = None;
+        process.start_time = None;
+        process.health = None;
+        process.status = if exit_status.success() || matches!(process.status, ProcessStatus::Stopping) {
+            ProcessStatus::Stopped
+        } else {
+            process.crash_count += 1;
+            process.record_crash(exit_status.code(), uptime, None);
+            ProcessStatus::Crashed
+        };
+
+        let mut restart_in = None;
+        if process.auto_restart && process.should_restart.load(Ordering::SeqCst) {
+            let policy = &process.restart_policy;
+            let now = now_ms();
+            if uptime >= policy.min_uptime_ms {
+                process.restart_attempts = 0;
+                process.restart_history.clear();
+            }
+            while process
+                .restart_history
+                .front()
+                .is_some_and(|&t| now.saturating_sub(t) > policy.retry_window_ms)
+            {
+                process.restart_history.pop_front();
+            }
+
+            if policy.max_retries > 0 && process.restart_history.len() >= policy.max_retries as usize {
+                process.status = ProcessStatus::Errored;
+                process.should_restart.store(false, Ordering::SeqCst);
+            } else {
+                process.restart_history.push_back(now);
+                process.restart_attempts += 1;
+                restart_in = Some(policy.delay_for(process.restart_attempts));
+            }
+        }
+
+        // Leftover group members would hold ports the respawn needs.
+        #[cfg(unix)]
+        if let (Some(pid), Some(_)) = (leader_pid, restart_in) {
+            let _ = signal_group(pid, libc::SIGKILL);
+        }
+        #[cfg(not(unix))]
+        let _ = leader_pid;
+
+        ExitCheck::Exited {
+            code: exit_status.code(),
+            status: process.status.clone(),
+            crash_count: process.crash_count,
+            restart_in,
+        }
+    }
+
+    /// Whether the child spawned as `run_id` is still the live one.
+    pub fn is_current(&self, id: &str, run_id: u64) -> bool {
+        self.get_process(id)
+            .is_some_and(|p| p.run_id == run_id && p.child.is_some())
+    }
+
+    /// Record a health transition for `run_id`. Returns true if it changed.
+    pub fn set_health(&mut self, id: &str, run_id: u64, health: HealthStatus) -> bool {
+        match self.get_process_mut(id) {
+            Some(p) if p.run_id == run_id && p.child.is_some() && p.health != Some(health) => {
+                p.health = Some(health);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether dependents may start: running, and healthy if it has a
+    /// readiness check. Errors once the process is no longer running.
+    pub fn is_ready(&self, id: &str) -> Result<bool, String> {
+        let process = self
+            .get_process(id)
+            .ok_or_else(|| "Process not found".to_string())?;
+        if !matches!(process.status, ProcessStatus::Running) {
+            return Err(format!("'{}' is not running ({})", process.name, process.status));
+        }
+        Ok(process.readiness.is_none() || process.health == Some(HealthStatus::Healthy))
+    }
+
+    /// Record that trigger `index` fires now, unless it fired within
+    /// `cooldown`. Returns whether it may fire.
+    pub fn claim_trigger(&mut self, id: &str, index: usize, cooldown: Duration) -> bool {
+        let Some(process) = self.get_process_mut(id) else {
+            return false;
+        };
+        let now = Instant::now();
+        match process.trigger_fired.get(&index) {
+            Some(last) if now.duration_since(*last) < cooldown => false,
+            _ => {
+                process.trigger_fired.insert(index, now);
+                true
+            }
+        }
+    }
+
+    /// Whether a supervisor should still respawn `run_id` after its exit.
+    pub fn wants_restart(&self, id: &str, run_id: u64) -> bool {
+        self.get_process(id)
+            .map(|p| {
+                p.run_id == run_id
+                    && p.child.is_none()
+                    && p.auto_restart
+                    && p.should_restart.load(Ordering::SeqCst)
+            })
+            .unwrap_or(false)
     }
 }
 
@@ -219,3 +817,97 @@ impl Default for ProcessManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_deps(deps: &[(&str, &[&str])]) -> (ProcessManager, HashMap<String, String>) {
+        let mut manager = ProcessManager::new();
+        let mut ids = HashMap::new();
+        for (name, _) in deps {
+            let id = manager.add_process(name.to_string(), "true".into(), vec![], None, false);
+            ids.insert(name.to_string(), id);
+        }
+        for (name, on) in deps {
+            let process = manager.get_process_mut(&ids[*name]).unwrap();
+            process.depends_on = on.iter().map(|d| d.to_string()).collect();
+        }
+        (manager, ids)
+    }
+
+    fn names(manager: &ProcessManager, levels: Vec<Vec<String>>) -> Vec<Vec<String>> {
+        levels
+            .into_iter()
+            .map(|level| level.iter().map(|id| manager.get_process(id).unwrap().name.clone()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn levels_follow_dependencies() {
+        let (manager, _) = with_deps(&[
+            ("web", &["api", "assets"]),
+            ("api", &["db", "cache"]),
+            ("db", &[]),
+            ("cache", &[]),
+            ("assets", &[]),
+        ]);
+        let levels = manager.dependency_levels().unwrap();
+        assert_eq!(names(&manager, levels), [vec!["assets", "cache", "db"], vec!["api"], vec!["web"]]);
+    }
+
+    #[test]
+    fn levels_accept_ids_and_skip_unresolved_refs() {
+        let (mut manager, ids) = with_deps(&[("api", &["missing"]), ("db", &[])]);
+        manager.get_process_mut(&ids["api"]).unwrap().depends_on.push(ids["db"].clone());
+        let levels = manager.dependency_levels().unwrap();
+        assert_eq!(names(&manager, levels), [vec!["db"], vec!["api"]]);
+        // The unresolved reference is kept on the process.
+        assert!(manager.get_process(&ids["api"]).unwrap().depends_on.contains(&"missing".to_string()));
+    }
+
+    #[test]
+    fn levels_of_only_include_transitive_deps() {
+        let (manager, ids) = with_deps(&[("web", &["api"]), ("api", &["db"]), ("db", &[]), ("other", &[])]);
+        let levels = manager.dependency_levels_of(&ids["web"]).unwrap();
+        assert_eq!(names(&manager, levels), [vec!["db"], vec!["api"]]);
+        assert!(manager.dependency_levels_of(&ids["db"]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cycles_are_reported_by_name() {
+        let (manager, _) = with_deps(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]), ("d", &[])]);
+        let error = manager.dependency_levels().unwrap_err();
+        assert!(error.starts_with("Dependency cycle: "), "{}", error);
+        let cycle: Vec<&str> = error["Dependency cycle: ".len()..].split(" -> ").collect();
+        assert_eq!(cycle.len(), 4);
+        assert_eq!(cycle.first(), cycle.last());
+        for name in ["a", "b", "c"] {
+            assert!(cycle.contains(&name));
+        }
+
+        let (manager, _) = with_deps(&[("self", &["self"])]);
+        assert_eq!(manager.dependency_levels().unwrap_err(), "Dependency cycle: self -> self");
+    }
+
+    #[test]
+    fn validate_dependencies_rejects_unknown_and_ambiguous() {
+        let (manager, _) = with_deps(&[("db", &[]), ("worker", &[]), ("worker", &[])]);
+        assert!(manager.validate_dependencies("api", &["db".into()]).is_ok());
+        assert!(manager
+            .validate_dependencies("api", &["nope".into()])
+            .unwrap_err()
+            .contains("Unknown process 'nope'"));
+        assert!(manager
+            .validate_dependencies("api", &["worker".into()])
+            .unwrap_err()
+            .contains("ambiguous"));
+    }
+
+    #[test]
+    fn removing_a_process_drops_references_to_it() {
+        let (mut manager, ids) = with_deps(&[("api", &["db"]), ("db", &[])]);
+        manager.remove_process(&ids["db"]);
+        assert!(manager.get_process(&ids["api"]).unwrap().depends_on.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Masking of secrets in captured output before it is logged or emitted.
+
+use regex::Regex;
+use std::borrow::Cow;
+
+pub const MASK: &str = "<redacted>";
+/// Secret values shorter than this would mask too much innocent text.
+const MIN_SECRET_LEN: usize = 4;
+
+#[derive(Debug, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    /// Literal values, longest first so a secret containing another is
+    /// masked whole.
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(patterns: &[String], secrets: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| format!("Invalid redaction pattern '{}': {}", p, e)))
+            .collect::<Result<_, _>>()?;
+        let mut secrets: Vec<String> = secrets
+            .into_iter()
+            .filter(|s| s.len() >= MIN_SECRET_LEN)
+            .collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Ok(Redactor { patterns, secrets })
+    }
+
+    /// Mask secret values, then pattern matches. A pattern with capture
+    /// groups masks only the groups, so `token=(\S+)` keeps `token=`.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), MASK));
+            }
+        }
+        for pattern in &self.patterns {
+            if pattern.is_match(&text) {
+                text = Cow::Owned(mask_matches(pattern, &text));
+            }
+        }
+        text
+    }
+}
+
+fn mask_matches(pattern: &Regex, text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for captures in pattern.captures_iter(text) {
+        let spans: Vec<_> = if pattern.captures_len() > 1 {
+            captures.iter().skip(1).flatten().collect()
+        } else {
+            captures.get(0).into_iter().collect()
+        };
+        for span in spans {
+            // Nested groups fall inside a span already masked.
+            if span.start() < last {
+                continue;
+            }
+            out.push_str(&text[last..span.start()]);
+            out.push_str(MASK);
+            last = span.end();
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(patterns: &[&str], secrets: &[&str]) -> Redactor {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        Redactor::new(&patterns, secrets.iter().map(|s| s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn masks_secret_values() {
+        let redactor = build(&[], &["hunter22", "hunter2200", "abc"]);
+        assert_eq!(redactor.redact("pw=hunter2200 or hunter22"), "pw=<redacted> or <redacted>");
+        // Too short to be masked.
+        assert_eq!(redactor.redact("abc"), "abc");
+    }
+
+    #[test]
+    fn masks_whole_pattern_matches() {
+        let redactor = build(&[r"sk_live_\w+"], &[]);
+        assert_eq!(redactor.redact("key sk_live_123 and sk_live_456."), "key <redacted> and <redacted>.");
+    }
+
+    #[test]
+    fn masks_only_capture_groups() {
+        let redactor = build(&[r"token=(\S+)", r"user=(\w+) pass=(\w+)"], &[]);
+        assert_eq!(redactor.redact("GET /?token=abc123 ok"), "GET /?token=<redacted> ok");
+        assert_eq!(redactor.redact("user=bob pass=secret"), "user=<redacted> pass=<redacted>");
+        // An optional group that didn't participate masks nothing.
+        let optional = build(&[r"id=(\d+)?x"], &[]);
+        assert_eq!(optional.redact("id=x id=42x"), "id=x id=<redacted>x");
+        // Nested groups are masked once, as the outer span.
+        let nested = build(&[r"key=((\w+)-(\w+))"], &[]);
+        assert_eq!(nested.redact("key=ab-cd"), "key=<redacted>");
+    }
+
+    #[test]
+    fn leaves_clean_text_borrowed() {
+        let redactor = build(&["secret"], &["password"]);
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+        assert!(matches!(Redactor::default().redact("password"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let error = Redactor::new(&["(unclosed".to_string()], Vec::new()).unwrap_err();
+        assert!(error.contains("(unclosed"));
+    }
+}
@@ -0,0 +1,1601 @@
+use crate::config_handler::{ConfigHandler, Revision};
+use crate::config_watcher::ConfigWatcher;
+use crate::daemon::DaemonClient;
+use crate::env_loader;
+use crate::health::{self, LogProbe};
+use crate::importers;
+use crate::log_handler::LogHandler;
+use crate::metrics_history::MetricsHistory;
+use crate::monitoring::{Monitoring, TreeSampler};
+use crate::output_reader;
+use crate::process_manager::{ExitCheck, ProcessInstance, ProcessManager};
+use crate::redact::Redactor;
+use crate::support_bundle;
+use crate::terminal::TerminalManager;
+use crate::triggers::{self, ActiveTrigger};
+use crate::watchdog::{Breach, Watchdog};
+use crate::workspace;
+use crate::types::{ConfigBackup, ConfigIssue, HealthCheck, HealthProbe, HealthStatus, ImportFormat, ImportReport, LimitAction, LogEntry, LogExportFormat, LogPage, LogPolicy, LogQuery, LogTrigger, MetricsResolution, MetricsSample, ProcessConfig, ProcessState, ProcessStatus, ResourceLimits, RestartPolicy, TriggerAction, WorkspaceInfo};
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{ChildStderr, ChildStdout};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::System;
+
+/// Destination for backend events: the GUI window when running in-process,
+/// or the subscribed clients when running as a daemon.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: serde_json::Value);
+}
+
+pub type Events = Arc<dyn EventSink>;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub manager: Arc<Mutex<ProcessManager>>,
+    pub log_handler: Arc<LogHandler>,
+    pub metrics: Arc<MetricsHistory>,
+    /// Persistent sysinfo System — keeps prior CPU snapshot so delta is accurate.
+    /// Only the managed process trees are kept refreshed.
+    pub system: Arc<Mutex<System>>,
+    /// Integrated terminal sessions.
+    pub terminal: Arc<Mutex<TerminalManager>>,
+    /// Set when the GUI is a client of a background daemon; process
+    /// commands are forwarded to it instead of running in-process.
+    pub daemon: Option<Arc<DaemonClient>>,
+    /// `processes.json` as last loaded or saved; `None` until then.
+    pub config_revision: Arc<Mutex<Option<Revision>>>,
+}
+
+impl AppState {
+    pub fn new(log_handler: LogHandler, metrics: MetricsHistory) -> Self {
+        let log_handler = Arc::new(log_handler);
+        LogHandler::start_flusher(&log_handler);
+
+        AppState {
+            manager: Arc::new(Mutex::new(ProcessManager::new())),
+            log_handler,
+            metrics: Arc::new(metrics),
+            system: Arc::new(Mutex::new(System::new())),
+            terminal: Arc::new(Mutex::new(TerminalManager::new())),
+            daemon: None,
+            config_revision: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// Sample every running process, summed over its descendants, into the
+/// metrics history each interval and push the readings, with the per-child
+/// breakdown, as `process:metrics` events. Resource limits are enforced
+/// from here too. The only place metrics
+/// are read from the system; everything else uses the history.
+pub fn start_metrics_sampler(state: &AppState, events: Events) {
+    let state = state.clone();
+    let interval = state.metrics.interval();
+    std::thread::spawn(move || {
+        let mut sampler = TreeSampler::default();
+        let mut watchdog = Watchdog::default();
+        // Disk counters are cumulative; rates come from the previous tick.
+        let mut previous: HashMap<u32, (u64, u64, Instant)> = HashMap::new();
+        loop {
+            std::thread::sleep(interval);
+
+            let running: Vec<(String, u32, ResourceLimits)> = match state.manager.lock() {
+                Ok(m) => m
+                    .processes
+                    .values()
+                    .filter_map(|p| p.pid.map(|pid| (p.id.clone(), pid, p.limits.clone())))
+                    .collect(),
+                Err(_) => return,
+            };
+            if running.is_empty() {
+                previous.clear();
+                watchdog = Watchdog::default();
+                continue;
+            }
+
+            let readings: Vec<_> = {
+                let Ok(mut system) = state.system.lock() else { return };
+                let roots: Vec<u32> = running.iter().map(|(_, pid, _)| *pid).collect();
+                sampler.refresh(&roots, &mut system);
+                running
+                    .into_iter()
+                    .filter_map(|(id, pid, limits)| {
+                        Monitoring::read_tree(pid, sampler.children(pid), &system)
+                            .map(|(total, children)| (id, pid, limits, total, children))
+                    })
+                    .collect()
+            };
+
+            let now = Instant::now();
+            let ts = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let mut seen = HashMap::new();
+            for (id, pid, limits, reading, children) in readings {
+                let (read_rate, write_rate) = match previous.get(&pid) {
+                    Some(&(read, written, at)) => {
+                        let secs = now.duration_since(at).as_secs_f64().max(0.001);
+                        (
+                            reading.disk_read_bytes.saturating_sub(read) as f64 / secs,
+                            reading.disk_written_bytes.saturating_sub(written) as f64 / secs,
+                        )
+                    }
+                    None => (0.0, 0.0),
+                };
+                seen.insert(pid, (reading.disk_read_bytes, reading.disk_written_bytes, now));
+
+                let sample = MetricsSample {
+                    ts,
+                    cpu_percent: reading.cpu_percent,
+                    cpu_max: reading.cpu_percent,
+                    memory_bytes: reading.memory_bytes,
+                    memory_max_bytes: reading.memory_bytes,
+                    memory_percent: reading.memory_percent,
+                    threads: reading.threads,
+                    open_fds: reading.open_fds,
+                    disk_read_bytes_per_sec: read_rate,
+                    disk_write_bytes_per_sec: write_rate,
+                };
+                events.emit(
+                    "process:metrics",
+                    json!({ "id": &id, "metrics": &sample, "children": &children }),
+                );
+                for breach in watchdog.check(pid, &limits, &sample, now) {
+                    enforce_limit(&state, &id, &breach, limits.action, &events);
+                }
+                // Under the manager lock, so a process removed meanwhile isn't
+                // given a fresh series after its history was forgotten.
+                if let Ok(mut manager) = state.manager.lock() {
+                    let Some(process) = manager.get_process_mut(&id) else { continue };
+                    let _ = state.metrics.record(&id, sample);
+                    if process.pid == Some(pid) {
+                        process.children = children;
+                    }
+                }
+            }
+            watchdog.retain(|pid| seen.contains_key(&pid));
+            previous = seen;
+        }
+    });
+}
+
+/// Report a crossed resource threshold and take the configured action.
+fn enforce_limit(state: &AppState, process_id: &str, breach: &Breach, action: LimitAction, events: &Events) {
+    let (name, run_id) = match state.manager.lock() {
+        Ok(m) => match m.get_process(process_id) {
+            Some(p) => (p.name.clone(), p.run_id),
+            None => return,
+        },
+        Err(_) => return,
+    };
+    let outcome = match action {
+        LimitAction::Warn => "",
+        LimitAction::Restart => "; restarting",
+        LimitAction::Stop => "; stopping",
+    };
+    let _ = state.log_handler.write_log(
+        process_id,
+        run_id,
+        "stderr",
+        &format!("[pm] {}{}", breach.describe(), outcome),
+    );
+    events.emit(
+        "process:limit_exceeded",
+        json!({
+            "id": process_id,
+            "name": &name,
+            "limit": breach.limit(),
+            "message": breach.describe(),
+            "action": action,
+        }),
+    );
+
+    match action {
+        LimitAction::Warn => {}
+        LimitAction::Restart => {
+            tauri::async_runtime::spawn(restart_gracefully(
+                state.clone(),
+                process_id.to_string(),
+                Arc::clone(events),
+                false,
+            ));
+        }
+        LimitAction::Stop => {
+            tauri::async_runtime::spawn(stop_gracefully(
+                Arc::clone(&state.manager),
+                process_id.to_string(),
+                Arc::clone(events),
+            ));
+        }
+    }
+}
+
+/// How often a supervisor polls its child for exit.
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How often a graceful stop checks whether the child has exited.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often dependency start-up checks whether a level is ready.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Give up waiting for a dependency to become ready after this long.
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Spawn background threads that read stdout/stderr from a child process,
+/// write each line to the log file, emit a real-time event and fire any
+/// matching triggers.
+#[allow(clippy::too_many_arguments)]
+fn start_log_readers(
+    process_id: String,
+    run_id: u64,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    encoding: &'static Encoding,
+    log_probes: Vec<Arc<LogProbe>>,
+    triggers: Arc<Vec<ActiveTrigger>>,
+    state: AppState,
+    events: Events,
+) {
+    let sources: [(&'static str, Box<dyn Read + Send>); 2] = [("stdout", Box::new(stdout)), ("stderr", Box::new(stderr))];
+    for (stream, source) in sources {
+        let id = process_id.clone();
+        let win = Arc::clone(&events);
+        let probes = log_probes.clone();
+        let triggers = Arc::clone(&triggers);
+        let state = state.clone();
+        output_reader::spawn_line_reader(source, encoding, move |msg| {
+            probes.iter().for_each(|p| p.observe(&msg));
+            if let Ok(entry) = state.log_handler.write_log(&id, run_id, stream, &msg) {
+                win.emit(&format!("process:log:{}", id), json!(entry));
+                for trigger in triggers.iter().filter(|t| t.matches(stream, &entry.message)) {
+                    fire_trigger(&state, &id, run_id, trigger, &entry.message, &win);
+                }
+            }
+        });
+    }
+}
+
+/// Run a matched trigger's actions unless it is still cooling down. Slow
+/// actions run in the background so the log reader keeps up.
+fn fire_trigger(state: &AppState, process_id: &str, run_id: u64, active: &ActiveTrigger, line: &str, events: &Events) {
+    let (name, working_dir) = match state.manager.lock() {
+        Ok(mut m) => {
+            if !m.claim_trigger(process_id, active.index, active.cooldown()) {
+                return;
+            }
+            match m.get_process(process_id) {
+                Some(p) => (p.name.clone(), p.working_dir.clone()),
+                None => return,
+            }
+        }
+        Err(_) => return,
+    };
+    let pattern = &active.trigger.pattern;
+    let _ = state.log_handler.write_log(
+        process_id,
+        run_id,
+        "stderr",
+        &format!("[pm] trigger '{}' fired", pattern),
+    );
+
+    for action in &active.trigger.actions {
+        match action {
+            TriggerAction::Event => events.emit(
+                "process:trigger",
+                json!({ "id": process_id, "name": &name, "pattern": pattern, "line": line }),
+            ),
+            TriggerAction::Notify { message } => events.emit(
+                "process:notification",
+                json!({ "id": process_id, "title": &name, "body": message.as_deref().unwrap_or(line) }),
+            ),
+            TriggerAction::Restart => {
+                tauri::async_runtime::spawn(restart_gracefully(
+                    state.clone(),
+                    process_id.to_string(),
+                    Arc::clone(events),
+                    false,
+                ));
+            }
+            TriggerAction::Stop => {
+                tauri::async_runtime::spawn(stop_gracefully(
+                    Arc::clone(&state.manager),
+                    process_id.to_string(),
+                    Arc::clone(events),
+                ));
+            }
+            TriggerAction::MarkReady => {
+                let changed = match state.manager.lock() {
+                    Ok(mut m) => m.set_health(process_id, run_id, HealthStatus::Healthy),
+                    Err(_) => false,
+                };
+                if changed {
+                    events.emit(
+                        "process:health_changed",
+                        json!({ "id": process_id, "health": HealthStatus::Healthy }),
+                    );
+                }
+            }
+            TriggerAction::Hook { command } => {
+                let (state, process_id, name, line, command, working_dir) = (
+                    state.clone(),
+                    process_id.to_string(),
+                    name.clone(),
+                    line.to_string(),
+                    command.clone(),
+                    working_dir.clone(),
+                );
+                std::thread::spawn(move || {
+                    let env = [
+                        ("PM_PROCESS_ID", process_id.as_str()),
+                        ("PM_PROCESS_NAME", name.as_str()),
+                        ("PM_TRIGGER_LINE", line.as_str()),
+                    ];
+                    if let Err(e) = triggers::run_hook(&command, working_dir.as_deref(), &env) {
+                        let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Spawn a background thread that reaps the child started as `run_id`,
+/// reports how it exited and respawns it according to its restart policy.
+fn start_supervisor(process_id: String, run_id: u64, state: AppState, events: Events) {
+    let manager = Arc::clone(&state.manager);
+    std::thread::spawn(move || {
+        let mut run_id = run_id;
+        loop {
+            std::thread::sleep(SUPERVISOR_POLL_INTERVAL);
+
+            let check = match manager.lock() {
+                Ok(mut m) => m.check_exit(&process_id, run_id),
+                Err(_) => return,
+            };
+
+            let restart_in = match check {
+                ExitCheck::Running => continue,
+                ExitCheck::Detached => return,
+                ExitCheck::Exited { code, status, crash_count, restart_in } => {
+                    events.emit(
+                        "process:status_changed",
+                        json!({
+                            "id": &process_id,
+                            "status": status.to_string(),
+                            "exitCode": code,
+                            "crashCount": crash_count,
+                            "restartInMs": restart_in.map(|d| d.as_millis() as u64),
+                        }),
+                    );
+                    restart_in
+                }
+            };
+            let Some(delay) = restart_in else {
+                return;
+            };
+
+            std::thread::sleep(delay);
+
+            let spawned = {
+                let mut m = match manager.lock() {
+                    Ok(m) => m,
+                    Err(_) => return,
+                };
+                // The user may have stopped or restarted it during the delay.
+                if !m.wants_restart(&process_id, run_id) {
+                    return;
+                }
+                m.respawn_process(&process_id)
+            }; // lock released here
+
+            match spawned {
+                Ok((pid, new_run_id, stdout, stderr)) => {
+                    events.emit(
+                        "process:status_changed",
+                        json!({ "id": &process_id, "status": "Running", "pid": pid }),
+                    );
+                    attach_run(process_id.clone(), new_run_id, stdout, stderr, &state, Arc::clone(&events));
+                    run_id = new_run_id;
+                }
+                Err(e) => {
+                    let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+                    events.emit(
+                        "process:status_changed",
+                        json!({ "id": &process_id, "status": "Crashed", "error": e }),
+                    );
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// A health check paired with its log matcher, for `log_line` probes.
+struct ActiveCheck {
+    check: HealthCheck,
+    log_probe: Option<Arc<LogProbe>>,
+}
+
+impl ActiveCheck {
+    fn new(check: HealthCheck) -> Result<Self, String> {
+        let log_probe = match &check.probe {
+            HealthProbe::LogLine { pattern } => Some(Arc::new(LogProbe::new(pattern)?)),
+            _ => None,
+        };
+        Ok(ActiveCheck { check, log_probe })
+    }
+}
+
+/// Spawn a background thread that probes readiness until it passes, then
+/// liveness for the rest of the run, publishing `process:health_changed`.
+/// Sustained liveness failure restarts the process if configured to.
+fn start_health_monitor(
+    process_id: String,
+    run_id: u64,
+    checks: Vec<(bool, ActiveCheck)>,
+    restart_on_unhealthy: bool,
+    working_dir: Option<String>,
+    state: AppState,
+    events: Events,
+) {
+    std::thread::spawn(move || {
+        for (is_readiness, active) in checks {
+            let check = &active.check;
+            std::thread::sleep(Duration::from_millis(check.initial_delay_ms));
+
+            let (mut passes, mut failures) = (0u32, 0u32);
+            loop {
+                let (current, healthy) = match state.manager.lock() {
+                    Ok(m) => (
+                        m.is_current(&process_id, run_id),
+                        m.get_process(&process_id)
+                            .is_some_and(|p| p.health == Some(HealthStatus::Healthy)),
+                    ),
+                    Err(_) => return,
+                };
+                if !current {
+                    return;
+                }
+                // A mark_ready trigger may have got there first.
+                if is_readiness && healthy {
+                    break;
+                }
+
+                let ok = health::run_probe(
+                    &check.probe,
+                    Duration::from_millis(check.timeout_ms),
+                    working_dir.as_deref(),
+                    active.log_probe.as_deref(),
+                );
+                if ok {
+                    passes += 1;
+                    failures = 0;
+                } else {
+                    failures += 1;
+                    passes = 0;
+                }
+
+                let next = if passes >= check.success_threshold.max(1) {
+                    Some(HealthStatus::Healthy)
+                } else if failures >= check.failure_threshold.max(1) {
+                    Some(HealthStatus::Unhealthy)
+                } else {
+                    None
+                };
+
+                if let Some(health) = next {
+                    let changed = match state.manager.lock() {
+                        Ok(mut m) => m.set_health(&process_id, run_id, health),
+                        Err(_) => return,
+                    };
+                    if changed {
+                        events.emit(
+                            "process:health_changed",
+                            json!({ "id": &process_id, "health": health }),
+                        );
+                    }
+                }
+
+                if is_readiness && next == Some(HealthStatus::Healthy) {
+                    break;
+                }
+                if !is_readiness && next == Some(HealthStatus::Unhealthy) && restart_on_unhealthy {
+                    let _ = state.log_handler.write_log(
+                        &process_id,
+                        run_id,
+                        "stderr",
+                        &format!("Liveness check failed {} times; restarting", failures),
+                    );
+                    tauri::async_runtime::spawn(restart_gracefully(
+                        state.clone(),
+                        process_id.clone(),
+                        Arc::clone(&events),
+                        false,
+                    ));
+                    return;
+                }
+
+                std::thread::sleep(Duration::from_millis(check.interval_ms));
+            }
+        }
+    });
+}
+
+/// Masks the process's `redact` patterns and the values of its secret env
+/// vars. An invalid pattern is returned as an error message, and the env
+/// values are still masked.
+fn redactor_for(process: &ProcessInstance) -> (Redactor, Option<String>) {
+    let secrets: Vec<String> = if process.secret_env.is_empty() {
+        Vec::new()
+    } else {
+        let env = env_loader::resolve_env(&process.env, process.env_file.as_deref(), process.working_dir.as_deref())
+            .unwrap_or_else(|_| process.env.clone().into_iter().collect());
+        process
+            .secret_env
+            .iter()
+            .filter_map(|name| match env.iter().find(|(key, _)| key == name) {
+                Some((_, value)) => Some(value.clone()),
+                None if !process.clear_env => std::env::var(name).ok(),
+                None => None,
+            })
+            .collect()
+    };
+    match Redactor::new(&process.log_policy.redact, secrets.clone()) {
+        Ok(redactor) => (redactor, None),
+        Err(e) => (Redactor::new(&[], secrets).unwrap_or_default(), Some(e)),
+    }
+}
+
+/// Start log capture and health probing for one run of a process.
+fn attach_run(
+    process_id: String,
+    run_id: u64,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    state: &AppState,
+    events: Events,
+) {
+    let (readiness, liveness, restart_on_unhealthy, working_dir, log_policy, output_encoding, redactor, triggers) =
+        match state.manager.lock() {
+            Ok(m) => match m.get_process(&process_id) {
+                Some(p) => (
+                    p.readiness.clone(),
+                    p.liveness.clone(),
+                    p.restart_on_unhealthy,
+                    p.working_dir.clone(),
+                    p.log_policy.clone(),
+                    p.output_encoding.clone(),
+                    redactor_for(p),
+                    p.triggers.clone(),
+                ),
+                None => Default::default(),
+            },
+            Err(_) => Default::default(),
+        };
+    state.log_handler.set_policy(&process_id, log_policy);
+    let (redactor, redact_error) = redactor;
+    state.log_handler.set_redactor(&process_id, redactor);
+    if let Some(e) = redact_error {
+        let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+    }
+
+    let encoding = match output_reader::encoding_for(output_encoding.as_deref()) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+            encoding_rs::UTF_8
+        }
+    };
+
+    let mut checks = Vec::new();
+    for (is_readiness, check) in [(true, readiness), (false, liveness)] {
+        let Some(check) = check else { continue };
+        match ActiveCheck::new(check) {
+            Ok(active) => checks.push((is_readiness, active)),
+            Err(e) => {
+                let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+            }
+        }
+    }
+    let log_probes = checks
+        .iter()
+        .filter_map(|(_, active)| active.log_probe.clone())
+        .collect();
+
+    let mut active_triggers = Vec::new();
+    for (index, trigger) in triggers.into_iter().enumerate() {
+        match ActiveTrigger::new(index, trigger) {
+            Ok(active) => active_triggers.push(active),
+            Err(e) => {
+                let _ = state.log_handler.write_log(&process_id, run_id, "stderr", &e);
+            }
+        }
+    }
+
+    start_log_readers(
+        process_id.clone(),
+        run_id,
+        stdout,
+        stderr,
+        encoding,
+        log_probes,
+        Arc::new(active_triggers),
+        state.clone(),
+        Arc::clone(&events),
+    );
+    if !checks.is_empty() {
+        start_health_monitor(
+            process_id,
+            run_id,
+            checks,
+            restart_on_unhealthy,
+            working_dir,
+            state.clone(),
+            events,
+        );
+    }
+}
+
+/// Wire up log capture, health checks and exit supervision for a freshly
+/// spawned child.
+fn attach_process(
+    process_id: String,
+    run_id: u64,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    state: &AppState,
+    events: Events,
+) {
+    attach_run(process_id.clone(), run_id, stdout, stderr, state, Arc::clone(&events));
+    start_supervisor(process_id, run_id, state.clone(), events);
+}
+
+/// Stop the process gracefully and start it again. A `manual` restart
+/// resets its crash history; an automatic one keeps counting.
+async fn restart_gracefully(
+    state: AppState,
+    process_id: String,
+    events: Events,
+    manual: bool,
+) -> Result<u32, String> {
+    stop_gracefully(
+        Arc::clone(&state.manager),
+        process_id.clone(),
+        Arc::clone(&events),
+    )
+    .await?;
+
+    let (pid, run_id, stdout, stderr) = {
+        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+        let (pid, run_id, stdout, stderr) = if manual {
+            manager.spawn_process(&process_id)?
+        } else {
+            manager.respawn_process(&process_id)?
+        };
+        events.emit(
+            "process:status_changed",
+            json!({ "id": &process_id, "status": "Running", "pid": pid }),
+        );
+        (pid, run_id, stdout, stderr)
+    }; // lock released here
+
+    attach_process(process_id, run_id, stdout, stderr, &state, events);
+
+    Ok(pid)
+}
+
+/// Start processes level by level, waiting for each level to become ready
+/// before moving on. Running processes are left alone; anything depending
+/// on a process that failed to start or become ready is skipped.
+async fn start_in_order(
+    state: AppState,
+    levels: Vec<Vec<String>>,
+    events: Events,
+) -> Result<(), String> {
+    let mut failed: Vec<(String, String)> = Vec::new();
+
+    for level in levels {
+        let spawned = {
+            let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+            let mut spawned = Vec::new();
+            for process_id in &level {
+                let blocked = manager
+                    .dependency_levels_of(process_id)?
+                    .iter()
+                    .flatten()
+                    .find(|dep| failed.iter().any(|(f, _)| f == *dep))
+                    .map(|dep| manager.get_process(dep).map(|p| p.name.clone()).unwrap_or_default());
+                if let Some(dep) = blocked {
+                    failed.push((process_id.clone(), format!("skipped, depends on '{}'", dep)));
+                    continue;
+                }
+
+                let running = manager
+                    .get_process(process_id)
+                    .is_some_and(|p| matches!(p.status, ProcessStatus::Running | ProcessStatus::Stopping));
+                if running {
+                    continue;
+                }
+                match manager.spawn_process(process_id) {
+                    Ok((pid, run_id, stdout, stderr)) => {
+                        events.emit(
+                            "process:status_changed",
+                            json!({ "id": process_id, "status": "Running", "pid": pid }),
+                        );
+                        spawned.push((process_id.clone(), run_id, stdout, stderr));
+                    }
+                    Err(e) => failed.push((process_id.clone(), e)),
+                }
+            }
+            spawned
+        }; // lock released here
+
+        for (process_id, run_id, stdout, stderr) in spawned {
+            attach_process(process_id, run_id, stdout, stderr, &state, Arc::clone(&events));
+        }
+
+        let mut waiting: Vec<String> = level
+            .into_iter()
+            .filter(|id| !failed.iter().any(|(f, _)| f == id))
+            .collect();
+        let deadline = Instant::now() + READY_TIMEOUT;
+        while !waiting.is_empty() {
+            {
+                let manager = state.manager.lock().map_err(|e| e.to_string())?;
+                waiting.retain(|id| match manager.is_ready(id) {
+                    Ok(ready) => !ready,
+                    Err(e) => {
+                        failed.push((id.clone(), e));
+                        false
+                    }
+                });
+            }
+            if waiting.is_empty() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                for id in waiting.drain(..) {
+                    failed.push((id, "did not become ready in time".to_string()));
+                }
+                break;
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+    let details: Vec<String> = failed
+        .iter()
+        .map(|(id, reason)| {
+            let name = manager.get_process(id).map(|p| p.name.clone()).unwrap_or_else(|| id.clone());
+            format!("{}: {}", name, reason)
+        })
+        .collect();
+    Err(format!("Some processes failed to start: {}", details.join("; ")))
+}
+
+/// Signal the process to stop, wait for it to exit without holding the
+/// manager lock, and kill it if it outlives its stop timeout. Descendants
+/// that escaped its process group are killed afterwards; any that survive
+/// are reported as an error.
+async fn stop_gracefully(
+    manager: Arc<Mutex<ProcessManager>>,
+    process_id: String,
+    events: Events,
+) -> Result<(), String> {
+    let pid = {
+        let m = manager.lock().map_err(|e| e.to_string())?;
+        m.get_process(&process_id).and_then(|p| p.pid)
+    };
+    // Process table scans, and the wait after killing stragglers, run off
+    // the async workers.
+    let descendants = match pid {
+        Some(pid) => tauri::async_runtime::spawn_blocking(move || Monitoring::descendant_pids(pid))
+            .await
+            .map_err(|e| e.to_string())?,
+        None => Vec::new(),
+    };
+
+    let pending = {
+        let mut m = manager.lock().map_err(|e| e.to_string())?;
+        m.begin_stop(&process_id)?
+    }; // lock released here
+
+    if let Some(pending) = pending {
+        events.emit(
+            "process:status_changed",
+            json!({ "id": &process_id, "status": "Stopping" }),
+        );
+
+        let deadline = Instant::now() + pending.timeout;
+        loop {
+            tokio::time::sleep(STOP_POLL_INTERVAL).await;
+            let mut m = manager.lock().map_err(|e| e.to_string())?;
+            if m.try_finish_stop(&process_id, pending.run_id) {
+                break;
+            }
+            if Instant::now() >= deadline {
+                m.stop_process(&process_id)?;
+                break;
+            }
+        }
+    }
+
+    events.emit(
+        "process:status_changed",
+        json!({ "id": &process_id, "status": "Stopped" }),
+    );
+
+    let survivors = tauri::async_runtime::spawn_blocking(move || Monitoring::kill_stragglers(&descendants))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !survivors.is_empty() {
+        return Err(format!(
+            "Process stopped but {} descendant(s) could not be killed: {:?}",
+            survivors.len(),
+            survivors
+        ));
+    }
+    Ok(())
+}
+
+/// Resolve a process reference, either its id or its unique name.
+pub fn resolve_process(state: &AppState, reference: &str) -> Result<String, String> {
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+    manager.resolve_ref(reference)
+}
+
+pub fn get_processes(state: &AppState) -> Result<Vec<ProcessState>, String> {
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+    Ok(manager.get_all_processes())
+}
+
+pub async fn start_process(
+    state: &AppState,
+    process_id: String,
+    with_dependencies: Option<bool>,
+    events: Events,
+) -> Result<u32, String> {
+    if with_dependencies.unwrap_or(false) {
+        let levels = {
+            let manager = state.manager.lock().map_err(|e| e.to_string())?;
+            manager.dependency_levels_of(&process_id)?
+        };
+        start_in_order(state.clone(), levels, Arc::clone(&events)).await?;
+    }
+
+    let (pid, run_id, stdout, stderr) = {
+        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+        let (pid, run_id, stdout, stderr) = manager.spawn_process(&process_id)?;
+        events.emit(
+            "process:status_changed",
+            json!({ "id": &process_id, "status": "Running", "pid": pid }),
+        );
+        (pid, run_id, stdout, stderr)
+    }; // lock released here
+
+    attach_process(process_id, run_id, stdout, stderr, state, events);
+
+    Ok(pid)
+}
+
+pub async fn stop_process(state: &AppState, process_id: String, events: Events) -> Result<(), String> {
+    stop_gracefully(
+        Arc::clone(&state.manager),
+        process_id,
+        events,
+    )
+    .await
+}
+
+pub async fn restart_process(state: &AppState, process_id: String, events: Events) -> Result<u32, String> {
+    restart_gracefully(state.clone(), process_id, events, true).await
+}
+
+pub fn add_process(
+    state: &AppState,
+    name: String,
+    command: String,
+    args: Vec<String>,
+    working_dir: Option<String>,
+    env: Option<HashMap<String, String>>,
+    env_file: Option<String>,
+) -> Result<String, String> {
+    let id = {
+        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+        let id = manager.add_process(name, command, args, working_dir, false);
+        if let Some(process) = manager.get_process_mut(&id) {
+            process.env = env.unwrap_or_default();
+            process.env_file = env_file.filter(|f| !f.is_empty());
+        }
+        id
+    };
+    autosave(state)?;
+    Ok(id)
+}
+
+/// Add the processes described by a docker-compose file, Procfile or PM2
+/// ecosystem file. Entries named like an existing process are skipped, and
+/// their dependents depend on that process instead. JavaScript ecosystem
+/// files are evaluated with Node only when `run_js` is set.
+pub fn import_processes(
+    state: &AppState,
+    path: &str,
+    format: Option<ImportFormat>,
+    run_js: bool,
+) -> Result<ImportReport, String> {
+    let imported = importers::import(Path::new(path), format, run_js)?;
+    let mut warnings = imported.warnings;
+    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+
+    let mut existing: HashMap<String, String> = HashMap::new();
+    for config in &imported.processes {
+        if let Some(process) = manager.processes.values().find(|p| p.name == config.name) {
+            warnings.push(format!("{}: skipped, a process with this name already exists", config.name));
+            existing.insert(config.id.clone(), process.id.clone());
+        }
+    }
+
+    let mut process_ids = Vec::new();
+    for config in imported.processes.iter().filter(|c| !existing.contains_key(&c.id)) {
+        let mut instance = ProcessInstance::from_config(config);
+        for dep in instance.depends_on.iter_mut() {
+            if let Some(id) = existing.get(dep) {
+                dep.clone_from(id);
+            }
+        }
+        process_ids.push(instance.id.clone());
+        manager.processes.insert(instance.id.clone(), instance);
+    }
+
+    if let Err(e) = manager.dependency_levels() {
+        for id in &process_ids {
+            manager.processes.remove(id);
+        }
+        return Err(e);
+    }
+    drop(manager);
+    autosave(state)?;
+
+    Ok(ImportReport {
+        format: imported.format,
+        process_ids,
+        warnings,
+    })
+}
+
+pub async fn remove_process(state: &AppState, process_id: String, events: Events) -> Result<(), String> {
+    stop_and_remove(state, process_id, events).await?;
+    autosave(state)
+}
+
+async fn stop_and_remove(state: &AppState, process_id: String, events: Events) -> Result<(), String> {
+    stop_gracefully(
+        Arc::clone(&state.manager),
+        process_id.clone(),
+        events,
+    )
+    .await
+    .ok();
+
+    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+    manager.remove_process(&process_id);
+    state.metrics.forget(&process_id);
+
+    Ok(())
+}
+
+/// Fields of a process that can be edited in place; `None` leaves a
+/// field unchanged.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessUpdate {
+    pub auto_restart: Option<bool>,
+    pub auto_start: Option<bool>,
+    /// Empty string clears the working dir.
+    pub working_dir: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+    /// Empty string removes the env file.
+    pub env_file: Option<String>,
+    pub clear_env: Option<bool>,
+    pub restart_policy: Option<RestartPolicy>,
+    pub depends_on: Option<Vec<String>>,
+    pub log_policy: Option<LogPolicy>,
+    /// Empty string goes back to UTF-8.
+    pub output_encoding: Option<String>,
+    pub secret_env: Option<Vec<String>>,
+    /// Takes effect from the next start.
+    pub triggers: Option<Vec<LogTrigger>>,
+    /// Hard limits take effect from the next start.
+    pub limits: Option<ResourceLimits>,
+}
+
+/// Edit a standalone process and save it. Workspace processes can't be
+/// edited here, as the change would be lost on the next reload.
+pub fn update_process(state: &AppState, process_id: &str, update: ProcessUpdate) -> Result<(), String> {
+    if let Some(label) = update.output_encoding.as_deref().filter(|l| !l.is_empty()) {
+        output_reader::encoding_for(Some(label))?;
+    }
+    if let Some(policy) = &update.log_policy {
+        Redactor::new(&policy.redact, [])?;
+    }
+    for (index, trigger) in update.triggers.iter().flatten().enumerate() {
+        ActiveTrigger::new(index, trigger.clone())?;
+    }
+    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+
+    // Workspace processes aren't saved to processes.json; their project
+    // file is what they are reloaded from.
+    if let Some(workspace) = manager.get_process(process_id).and_then(|p| p.workspace.clone()) {
+        let file = manager
+            .workspaces
+            .get(&workspace)
+            .map_or(workspace, |path| path.display().to_string());
+        return Err(format!("This process is defined in {}; edit it there", file));
+    }
+
+    if let Some(deps) = update.depends_on {
+        let name = manager.get_process(process_id).map(|p| p.name.clone()).unwrap_or_default();
+        manager.validate_dependencies(&name, &deps)?;
+        let previous = match manager.get_process_mut(process_id) {
+            Some(process) => std::mem::replace(&mut process.depends_on, deps),
+            None => return Err("Process not found".to_string()),
+        };
+        if let Err(e) = manager.dependency_levels() {
+            if let Some(process) = manager.get_process_mut(process_id) {
+                process.depends_on = previous;
+            }
+            return Err(e);
+        }
+    }
+
+    if let Some(process) = manager.get_process_mut(process_id) {
+        if let Some(ar) = update.auto_restart {
+            process.auto_restart = ar;
+        }
+        if let Some(as_val) = update.auto_start {
+            process.auto_start = as_val;
+        }
+        // empty string means "clear working dir"
+        match update.working_dir {
+            Some(ref dir) if dir.is_empty() => process.working_dir = None,
+            Some(dir) => process.working_dir = Some(dir),
+            None => {}
+        }
+        if let Some(vars) = update.env {
+            process.env = vars;
+        }
+        // empty string means "no env file"
+        match update.env_file {
+            Some(ref file) if file.is_empty() => process.env_file = None,
+            Some(file) => process.env_file = Some(file),
+            None => {}
+        }
+        if let Some(clear) = update.clear_env {
+            process.clear_env = clear;
+        }
+        if let Some(policy) = update.restart_policy {
+            process.restart_policy = policy;
+        }
+        if let Some(policy) = update.log_policy {
+            state.log_handler.set_policy(process_id, policy.clone());
+            process.log_policy = policy;
+        }
+        match update.output_encoding {
+            Some(ref label) if label.is_empty() => process.output_encoding = None,
+            Some(label) => process.output_encoding = Some(label),
+            None => {}
+        }
+        if let Some(names) = update.secret_env {
+            process.secret_env = names;
+        }
+        if let Some(triggers) = update.triggers {
+            process.triggers = triggers;
+            process.trigger_fired.clear();
+        }
+        if let Some(limits) = update.limits {
+            process.limits = limits;
+        }
+        // Env and patterns may have changed; mask accordingly from now on.
+        state.log_handler.set_redactor(process_id, redactor_for(process).0);
+    }
+    drop(manager);
+
+    autosave(state)
+}
+
+/// The latest reading of a running process, summed over its descendants,
+/// with each descendant's own usage under `children`; as last pushed in
+/// `process:metrics`.
+pub fn get_metrics(state: &AppState, process_id: &str) -> Result<serde_json::Value, String> {
+    let children = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        manager
+            .get_process(process_id)
+            .filter(|p| p.pid.is_some())
+            .map(|p| p.children.clone())
+    };
+
+    if let Some(children) = children {
+        if let Some(sample) = state.metrics.latest(process_id) {
+            return Ok(json!({
+                "cpu_percent": sample.cpu_percent,
+                "memory_mb": sample.memory_bytes.div_ceil(1024 * 1024),
+                "memory_percent": sample.memory_percent,
+                "children": children,
+            }));
+        }
+    }
+
+    Ok(json!({
+        "cpu_percent": 0.0,
+        "memory_mb": 0,
+        "memory_percent": 0.0,
+        "children": [],
+    }))
+}
+
+pub fn get_metrics_history(
+    state: &AppState,
+    process_id: &str,
+    from: Option<u64>,
+    to: Option<u64>,
+    resolution: MetricsResolution,
+) -> Result<Vec<MetricsSample>, String> {
+    state
+        .metrics
+        .query(process_id, from, to, resolution)
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_logs(state: &AppState, process_id: &str) -> Result<Vec<LogEntry>, String> {
+    state
+        .log_handler
+        .read_logs(process_id, Some(1000))
+        .map_err(|e| e.to_string())
+}
+
+pub fn query_logs(state: &AppState, process_id: &str, query: &LogQuery) -> Result<LogPage, String> {
+    state
+        .log_handler
+        .query_logs(process_id, query)
+        .map_err(|e| e.to_string())
+}
+
+/// Write a process's matching log entries to `path`; returns how many.
+pub fn export_logs(
+    state: &AppState,
+    process_id: &str,
+    query: &LogQuery,
+    format: LogExportFormat,
+    path: &str,
+) -> Result<usize, String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    state
+        .log_handler
+        .export_logs(process_id, query, format, &mut std::io::BufWriter::new(file))
+        .map_err(|e| e.to_string())
+}
+
+/// Zip configs, state, metrics, crash history and logs of `process_ids`
+/// (all processes when empty) into `path`.
+pub fn create_support_bundle(
+    state: &AppState,
+    process_ids: &[String],
+    since: Option<String>,
+    path: &str,
+) -> Result<(), String> {
+    support_bundle::create(state, process_ids, since, std::path::Path::new(path))
+}
+
+pub fn clear_logs(state: &AppState, process_id: &str) -> Result<(), String> {
+    state
+        .log_handler
+        .clear_logs(process_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Save the standalone processes. Fails if `processes.json` was changed
+/// by someone else since it was loaded, unless `force` is set.
+pub fn save_config(state: &AppState, force: bool) -> Result<(), String> {
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+
+    // Workspace processes belong to their project files.
+    let configs: Vec<ProcessConfig> = manager
+        .processes
+        .values()
+        .filter(|p| p.workspace.is_none())
+        .map(|p| p.to_config())
+        .collect();
+    drop(manager);
+
+    let mut revision = state.config_revision.lock().map_err(|e| e.to_string())?;
+    let expected = if force { None } else { *revision };
+    *revision = Some(ConfigHandler::save_configs(&configs, expected).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Save after a change made through a command. Skipped until the config
+/// has been loaded, so an early change can't replace the saved processes.
+fn autosave(state: &AppState) -> Result<(), String> {
+    if state.config_revision.lock().map_err(|e| e.to_string())?.is_none() {
+        return Ok(());
+    }
+    save_config(state, false).map_err(|e| format!("Changed, but not saved: {}", e))
+}
+
+/// Load saved processes and reopen the workspaces that were open. The
+/// standalone processes are synced to the file (see [`sync_processes`]),
+/// so a reconnecting GUI never clobbers a running child.
+pub async fn load_config(state: &AppState, events: Events) -> Result<Vec<ProcessState>, String> {
+    let (loaded, revision) = ConfigHandler::load_configs().map_err(|e| e.to_string())?;
+    for issue in &loaded.issues {
+        eprintln!("processes.json: {}", issue);
+    }
+    *state.config_revision.lock().map_err(|e| e.to_string())? = Some(revision);
+    sync_processes(state, None, loaded.processes, events).await?;
+
+    // A broken project file shouldn't keep the rest from loading.
+    for path in ConfigHandler::load_workspaces().map_err(|e| e.to_string())? {
+        if let Err(e) = load_workspace(state, &path) {
+            eprintln!("Skipping workspace {}: {}", path.display(), e);
+        }
+    }
+
+    get_processes(state)
+}
+
+/// Problems with the saved `processes.json`, per process: entries that
+/// can't be loaded, unknown fields, missing programs and directories.
+pub fn check_config() -> Result<Vec<ConfigIssue>, String> {
+    Ok(ConfigHandler::load_configs().map_err(|e| e.to_string())?.0.issues)
+}
+
+pub fn list_config_backups() -> Result<Vec<ConfigBackup>, String> {
+    ConfigHandler::list_backups().map_err(|e| e.to_string())
+}
+
+/// Make a backup the current `processes.json` and apply it: processes it
+/// doesn't have are stopped and removed, the others take its config.
+pub async fn restore_config_backup(state: &AppState, name: &str, events: Events) -> Result<Vec<ProcessState>, String> {
+    let expected = *state.config_revision.lock().map_err(|e| e.to_string())?;
+    let (loaded, revision) = ConfigHandler::restore_backup(name, expected).map_err(|e| e.to_string())?;
+    *state.config_revision.lock().map_err(|e| e.to_string())? = Some(revision);
+
+    sync_processes(state, None, loaded.processes, events).await?;
+    get_processes(state)
+}
+
+/// Make the processes of `workspace` (`None` for the standalone ones)
+/// match `configs`: new ones are added and missing ones stopped and
+/// removed. The rest take their new config; running ones restart if what
+/// they are spawned with changed, otherwise it applies from the next start.
+/// Nothing changes if the result has a dependency cycle.
+async fn sync_processes(
+    state: &AppState,
+    workspace: Option<&str>,
+    configs: Vec<ProcessConfig>,
+    events: Events,
+) -> Result<(), String> {
+    let (removed, changed) = {
+        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+        if let Some(config) = configs.iter().find(|c| {
+            manager
+                .processes
+                .get(&c.id)
+                .is_some_and(|p| p.workspace.as_deref() != workspace)
+        }) {
+            return Err(format!("A process with id '{}' already exists", config.id));
+        }
+
+        let previous: Vec<ProcessConfig> = configs
+            .iter()
+            .filter_map(|c| manager.processes.get(&c.id).map(|p| p.to_config()))
+            .collect();
+        let mut inserted = Vec::new();
+        let mut changed = Vec::new();
+        for config in &configs {
+            match manager.processes.get_mut(&config.id) {
+                Some(process) => {
+                    if matches!(process.status, ProcessStatus::Running) && process.spawn_differs(config) {
+                        changed.push(config.id.clone());
+                    }
+                    process.apply_config(config);
+                }
+                None => {
+                    let mut instance = ProcessInstance::from_config(config);
+                    instance.workspace = workspace.map(str::to_string);
+                    inserted.push(instance.id.clone());
+                    manager.processes.insert(instance.id.clone(), instance);
+                }
+            }
+        }
+
+        if let Err(e) = manager.dependency_levels() {
+            for id in &inserted {
+                manager.processes.remove(id);
+            }
+            for config in &previous {
+                if let Some(process) = manager.processes.get_mut(&config.id) {
+                    process.apply_config(config);
+                }
+            }
+            return Err(e);
+        }
+
+        // Like `update_process`, log policy and masking apply right away.
+        for process in configs.iter().filter_map(|c| manager.processes.get(&c.id)) {
+            state.log_handler.set_policy(&process.id, process.log_policy.clone());
+            state.log_handler.set_redactor(&process.id, redactor_for(process).0);
+        }
+
+        let removed: Vec<String> = manager
+            .processes
+            .values()
+            .filter(|p| p.workspace.as_deref() == workspace && !configs.iter().any(|c| c.id == p.id))
+            .map(|p| p.id.clone())
+            .collect();
+        (removed, changed)
+    };
+
+    for id in removed {
+        stop_and_remove(state, id, events.clone()).await?;
+    }
+    for id in changed {
+        restart_process(state, id, events.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Apply `processes.json` after it was edited outside the app. Returns
+/// false when it still holds what was last loaded or saved, as after our
+/// own saves, or when it hasn't been loaded yet.
+async fn reload_config(state: &AppState, events: Events) -> Result<bool, String> {
+    let (loaded, revision) = ConfigHandler::load_configs().map_err(|e| e.to_string())?;
+    {
+        let mut known = state.config_revision.lock().map_err(|e| e.to_string())?;
+        if known.is_none_or(|known| known == revision) {
+            return Ok(false);
+        }
+        *known = Some(revision);
+    }
+    for issue in &loaded.issues {
+        eprintln!("processes.json: {}", issue);
+    }
+    sync_processes(state, None, loaded.processes, events).await?;
+    Ok(true)
+}
+
+/// Apply an open project file after it was edited. If it now names a
+/// different workspace, the old one is closed and the file reopened.
+async fn reload_workspace(state: &AppState, name: &str, path: &Path, events: Events) -> Result<(), String> {
+    let loaded = workspace::load(path)?;
+    if loaded.name != name {
+        close_workspace(state, name, events).await?;
+        load_workspace(state, path)?;
+        return save_workspaces(state);
+    }
+    sync_processes(state, Some(name), loaded.processes, events).await
+}
+
+/// Watch `processes.json` and the open project files, apply edits made to
+/// them outside the app, and report each as `config:reloaded` or
+/// `config:reload_failed`.
+pub fn start_config_watcher(state: &AppState, events: Events) {
+    let state = state.clone();
+    std::thread::spawn(move || {
+        let (mut watcher, config_file) = match (ConfigWatcher::new(), ConfigHandler::get_config_file()) {
+            (Ok(watcher), Ok(config_file)) => (watcher, config_file),
+            (Err(e), _) => return eprintln!("Config files won't be reloaded: {}", e),
+            (_, Err(e)) => return eprintln!("Config files won't be reloaded: {}", e),
+        };
+        loop {
+            let workspaces: Vec<(String, PathBuf)> = match state.manager.lock() {
+                Ok(m) => m.workspaces.iter().map(|(name, path)| (name.clone(), path.clone())).collect(),
+                Err(_) => return,
+            };
+            let mut files = vec![config_file.clone()];
+            files.extend(workspaces.iter().map(|(_, path)| path.clone()));
+            watcher.watch(&files);
+
+            for file in watcher.changed(&files, Duration::from_secs(1)) {
+                let result = match workspaces.iter().find(|(_, path)| *path == file) {
+                    Some((name, path)) => {
+                        tauri::async_runtime::block_on(reload_workspace(&state, name, path, events.clone())).map(|()| true)
+                    }
+                    None => tauri::async_runtime::block_on(reload_config(&state, events.clone())),
+                };
+                match result {
+                    Ok(false) => {}
+                    Ok(true) => events.emit("config:reloaded", json!({ "path": file })),
+                    Err(e) => {
+                        eprintln!("Failed to reload {}: {}", file.display(), e);
+                        events.emit("config:reload_failed", json!({ "path": file, "message": e }));
+                    }
+                }
+            }
+        }
+    });
+}
+
+pub async fn start_all(state: &AppState, events: Events) -> Result<(), String> {
+    let levels = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        manager.dependency_levels()?
+    };
+
+    start_in_order(state.clone(), levels, events).await
+}
+
+pub async fn stop_all(state: &AppState, events: Events) -> Result<(), String> {
+    let levels = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        manager.dependency_levels()?
+    };
+
+    stop_in_order(state, levels, events).await
+}
+
+/// Stop processes in reverse dependency order: dependents before their
+/// dependencies, each level concurrently so one slow process doesn't delay
+/// its siblings.
+async fn stop_in_order(state: &AppState, levels: Vec<Vec<String>>, events: Events) -> Result<(), String> {
+    for level in levels.into_iter().rev() {
+        let handles: Vec<_> = level
+            .into_iter()
+            .map(|process_id| {
+                tauri::async_runtime::spawn(stop_gracefully(
+                    Arc::clone(&state.manager),
+                    process_id,
+                    Arc::clone(&events),
+                ))
+            })
+            .collect();
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Start every auto-start process, along with whatever it depends on.
+pub async fn start_auto_start(state: &AppState, events: Events) -> Result<(), String> {
+    let levels = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        let mut needed: HashSet<String> = HashSet::new();
+        for process in manager.processes.values().filter(|p| p.auto_start) {
+            needed.insert(process.id.clone());
+            needed.extend(manager.dependency_levels_of(&process.id)?.into_iter().flatten());
+        }
+        filter_levels(manager.dependency_levels()?, &needed)
+    };
+
+    start_in_order(state.clone(), levels, events).await
+}
+
+/// Dependency levels keeping only the processes in `keep`.
+fn filter_levels(levels: Vec<Vec<String>>, keep: &HashSet<String>) -> Vec<Vec<String>> {
+    levels
+        .into_iter()
+        .map(|level| level.into_iter().filter(|id| keep.contains(id)).collect::<Vec<_>>())
+        .filter(|level| !level.is_empty())
+        .collect()
+}
+
+fn workspace_info(manager: &ProcessManager, name: &str) -> Option<WorkspaceInfo> {
+    manager.workspaces.get(name).map(|path| WorkspaceInfo {
+        name: name.to_string(),
+        path: path.to_string_lossy().to_string(),
+        process_ids: manager.workspace_processes(name),
+    })
+}
+
+/// Remember the open workspaces for the next launch.
+fn save_workspaces(state: &AppState) -> Result<(), String> {
+    let paths: Vec<PathBuf> = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        manager.workspaces.values().cloned().collect()
+    };
+    ConfigHandler::save_workspaces(&paths).map_err(|e| e.to_string())
+}
+
+fn load_workspace(state: &AppState, path: &Path) -> Result<WorkspaceInfo, String> {
+    let loaded = workspace::load(path)?;
+    let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+    match manager.workspaces.get(&loaded.name) {
+        Some(open) if *open == loaded.path => {
+            return workspace_info(&manager, &loaded.name).ok_or_else(|| "Workspace not found".to_string());
+        }
+        Some(open) => {
+            return Err(format!(
+                "A workspace named '{}' is already open from {}",
+                loaded.name,
+                open.display()
+            ))
+        }
+        None => {}
+    }
+    if let Some(config) = loaded.processes.iter().find(|c| manager.processes.contains_key(&c.id)) {
+        return Err(format!("A process with id '{}' already exists", config.id));
+    }
+
+    for config in &loaded.processes {
+        let mut instance = ProcessInstance::from_config(config);
+        instance.workspace = Some(loaded.name.clone());
+        manager.processes.insert(instance.id.clone(), instance);
+    }
+    manager.workspaces.insert(loaded.name.clone(), loaded.path.clone());
+
+    if let Err(e) = manager.dependency_levels() {
+        for config in &loaded.processes {
+            manager.processes.remove(&config.id);
+        }
+        manager.workspaces.remove(&loaded.name);
+        return Err(e);
+    }
+    workspace_info(&manager, &loaded.name).ok_or_else(|| "Workspace not found".to_string())
+}
+
+/// Open the project file at `path`, or the `pm.toml` in that directory, as
+/// a workspace; it is reopened on the next launch. Opening a workspace that
+/// is already open returns it unchanged.
+pub fn open_workspace(state: &AppState, path: &str) -> Result<WorkspaceInfo, String> {
+    let info = load_workspace(state, Path::new(path))?;
+    save_workspaces(state)?;
+    Ok(info)
+}
+
+/// Stop and unload a workspace's processes. Their logs are kept for when
+/// it is opened again.
+pub async fn close_workspace(state: &AppState, name: &str, events: Events) -> Result<(), String> {
+    workspace_down(state, name, events).await?;
+
+    {
+        let mut manager = state.manager.lock().map_err(|e| e.to_string())?;
+        for id in manager.workspace_processes(name) {
+            manager.remove_process(&id);
+            state.metrics.forget(&id);
+        }
+        manager.workspaces.remove(name);
+    }
+    save_workspaces(state)
+}
+
+pub fn list_workspaces(state: &AppState) -> Result<Vec<WorkspaceInfo>, String> {
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+    Ok(manager
+        .workspaces
+        .keys()
+        .filter_map(|name| workspace_info(&manager, name))
+        .collect())
+}
+
+fn workspace_levels(state: &AppState, name: &str) -> Result<Vec<Vec<String>>, String> {
+    let manager = state.manager.lock().map_err(|e| e.to_string())?;
+    if !manager.workspaces.contains_key(name) {
+        return Err(format!("Unknown workspace '{}'", name));
+    }
+    let ids: HashSet<String> = manager.workspace_processes(name).into_iter().collect();
+    Ok(filter_levels(manager.dependency_levels()?, &ids))
+}
+
+/// Start every process of a workspace in dependency order.
+pub async fn workspace_up(state: &AppState, name: &str, events: Events) -> Result<(), String> {
+    let levels = workspace_levels(state, name)?;
+    start_in_order(state.clone(), levels, events).await
+}
+
+/// Stop every process of a workspace, dependents first.
+pub async fn workspace_down(state: &AppState, name: &str, events: Events) -> Result<(), String> {
+    let levels = workspace_levels(state, name)?;
+    stop_in_order(state, levels, events).await
+}
@@ -0,0 +1,135 @@
+//! Support bundles: a single zip to attach to a bug report.
+//!
+//! ```text
+//! manifest.json            app version, platform and the included processes
+//! <name>-<id>/config.json  the saved config, env values redacted
+//! <name>-<id>/state.json   status, pid, uptime, crash count
+//! <name>-<id>/metrics.json current CPU / memory, if running, and the last
+//!                          hour of history at one-minute resolution
+//! <name>-<id>/crashes.json recent crashes, oldest first
+//! <name>-<id>/logs.jsonl   log records, rotated segments included
+//! ```
+
+use crate::service::{self, AppState};
+use crate::types::{CrashRecord, LogExportFormat, LogQuery, MetricsResolution, ProcessConfig, ProcessState};
+use serde::Serialize;
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const REDACTED: &str = "<redacted>";
+/// How far back `metrics.json` reaches.
+const METRICS_WINDOW_MS: u64 = 60 * 60 * 1000;
+
+struct Snapshot {
+    dir: String,
+    config: ProcessConfig,
+    state: ProcessState,
+    crashes: Vec<CrashRecord>,
+}
+
+/// Directory name for a process: its name made path-safe, plus a short id
+/// so processes sharing a name don't collide.
+fn dir_name(name: &str, id: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}-{}", safe, id.get(..8).unwrap_or(id))
+}
+
+fn add_json<T: Serialize>(zip: &mut ZipWriter<File>, name: &str, value: &T) -> Result<(), String> {
+    zip.start_file(name, options()).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut *zip, value).map_err(|e| e.to_string())
+}
+
+fn options() -> SimpleFileOptions {
+    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+/// Write a bundle for `process_ids` (every process when empty) to `path`,
+/// with logs from `since` onwards.
+pub fn create(state: &AppState, process_ids: &[String], since: Option<String>, path: &Path) -> Result<(), String> {
+    let snapshots: Vec<Snapshot> = {
+        let manager = state.manager.lock().map_err(|e| e.to_string())?;
+        let mut processes: Vec<_> = if process_ids.is_empty() {
+            manager.processes.values().collect()
+        } else {
+            process_ids
+                .iter()
+                .map(|id| manager.get_process(id).ok_or_else(|| format!("Process not found: {}", id)))
+                .collect::<Result<_, _>>()?
+        };
+        processes.sort_by(|a, b| a.name.cmp(&b.name));
+        processes
+            .into_iter()
+            .map(|p| {
+                let mut config = p.to_config();
+                let mut state = p.to_state();
+                if let Some(env) = config.env.as_mut() {
+                    env.values_mut().for_each(|v| *v = REDACTED.to_string());
+                }
+                state.env.values_mut().for_each(|v| *v = REDACTED.to_string());
+                Snapshot {
+                    dir: dir_name(&p.name, &p.id),
+                    config,
+                    state,
+                    crashes: p.crash_history.iter().cloned().collect(),
+                }
+            })
+            .collect()
+    };
+
+    let partial = path.with_extension("zip.partial");
+    let mut zip = ZipWriter::new(File::create(&partial).map_err(|e| e.to_string())?);
+
+    let manifest = json!({
+        "created": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "logs_since": since,
+        "processes": snapshots
+            .iter()
+            .map(|s| json!({ "id": s.config.id, "name": s.config.name, "dir": s.dir }))
+            .collect::<Vec<_>>(),
+    });
+    add_json(&mut zip, "manifest.json", &manifest)?;
+
+    let query = LogQuery {
+        since,
+        ..LogQuery::default()
+    };
+    let metrics_from = (chrono::Utc::now().timestamp_millis() as u64).saturating_sub(METRICS_WINDOW_MS);
+    for snapshot in &snapshots {
+        let id = &snapshot.config.id;
+        add_json(&mut zip, &format!("{}/config.json", snapshot.dir), &snapshot.config)?;
+        add_json(&mut zip, &format!("{}/state.json", snapshot.dir), &snapshot.state)?;
+        add_json(
+            &mut zip,
+            &format!("{}/metrics.json", snapshot.dir),
+            &json!({
+                "current": service::get_metrics(state, id).unwrap_or_default(),
+                "history": service::get_metrics_history(state, id, Some(metrics_from), None, MetricsResolution::Minute)
+                    .unwrap_or_default(),
+            }),
+        )?;
+        add_json(&mut zip, &format!("{}/crashes.json", snapshot.dir), &snapshot.crashes)?;
+
+        zip.start_file(format!("{}/logs.jsonl", snapshot.dir), options().large_file(true))
+            .map_err(|e| e.to_string())?;
+        if let Err(e) = state
+            .log_handler
+            .export_logs(id, &query, LogExportFormat::Jsonl, &mut zip)
+        {
+            // A broken log shouldn't cost the rest of the bundle.
+            writeln!(zip, "{}", json!({ "error": e.to_string() })).map_err(|e| e.to_string())?;
+        }
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    fs::rename(&partial, path).map_err(|e| e.to_string())
+}
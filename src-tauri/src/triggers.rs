@@ -0,0 +1,75 @@
+use crate::types::LogTrigger;
+use regex::Regex;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+/// Hooks still running after this long are killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A trigger with its pattern compiled, for one run of a process.
+pub struct ActiveTrigger {
+    /// Position in the process's `triggers`; keys its cooldown.
+    pub index: usize,
+    pub trigger: LogTrigger,
+    pattern: Regex,
+}
+
+impl ActiveTrigger {
+    pub fn new(index: usize, trigger: LogTrigger) -> Result<Self, String> {
+        let pattern = Regex::new(&trigger.pattern)
+            .map_err(|e| format!("Invalid trigger pattern '{}': {}", trigger.pattern, e))?;
+        Ok(ActiveTrigger { index, trigger, pattern })
+    }
+
+    pub fn matches(&self, stream: &str, line: &str) -> bool {
+        self.trigger.stream.as_deref().is_none_or(|s| s == stream) && self.pattern.is_match(line)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_millis(self.trigger.cooldown_ms)
+    }
+}
+
+/// Run a hook through the shell (PowerShell on Windows) and wait for it.
+pub fn run_hook(command: &str, working_dir: Option<&str>, env: &[(&str, &str)]) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", command]);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    cmd.envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to run trigger hook: {}", e))?;
+    let deadline = Instant::now() + HOOK_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("Trigger hook exited with {}", status)),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(100)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Trigger hook timed out after {}s", HOOK_TIMEOUT.as_secs()));
+            }
+        }
+    }
+}
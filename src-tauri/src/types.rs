@@ -2,29 +2,400 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessConfig {
+    #[serde(default = "new_process_id")]
     pub id: String,
     pub name: String,
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
+    #[serde(default)]
     pub auto_restart: bool,
+    #[serde(default)]
     pub auto_start: bool,
+    #[serde(default)]
     pub working_dir: Option<String>,
+    #[serde(default)]
     pub env: Option<std::collections::HashMap<String, String>>,
+    /// Dotenv file loaded before `env`; relative paths resolve against `working_dir`.
+    #[serde(default)]
+    pub env_file: Option<String>,
+    /// Start from an empty environment instead of inheriting the app's.
+    #[serde(default)]
+    pub clear_env: bool,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    /// Signal sent first when stopping; escalates to a kill after `stop_timeout_ms`.
+    #[serde(default)]
+    pub stop_signal: StopSignal,
+    #[serde(default = "default_stop_timeout_ms")]
+    pub stop_timeout_ms: u64,
+    /// Ids or names of processes that must be started before this one.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Must pass before the process counts as ready (and dependents start).
+    #[serde(default)]
+    pub readiness: Option<HealthCheck>,
+    /// Probed once ready; sustained failure marks the process unhealthy.
+    #[serde(default)]
+    pub liveness: Option<HealthCheck>,
+    #[serde(default)]
+    pub restart_on_unhealthy: bool,
+    #[serde(default)]
+    pub log_policy: LogPolicy,
+    /// Encoding of the process's output, e.g. "windows-1252"; UTF-8 if unset.
+    #[serde(default)]
+    pub output_encoding: Option<String>,
+    /// Env vars (from `env`, `env_file` or inherited) whose values are
+    /// masked in the logs.
+    #[serde(default)]
+    pub secret_env: Vec<String>,
+    /// Actions run when a logged line matches a pattern.
+    #[serde(default)]
+    pub triggers: Vec<LogTrigger>,
+    #[serde(default)]
+    pub limits: ResourceLimits,
+}
+
+fn new_process_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HealthProbe {
+    /// A TCP connection to localhost:port succeeds.
+    Tcp { port: u16 },
+    /// GET http://localhost:port/path returns a 2xx status.
+    Http {
+        port: u16,
+        #[serde(default = "default_http_path")]
+        path: String,
+    },
+    /// A captured output line matches the regex.
+    LogLine { pattern: String },
+    /// The command exits with status 0.
+    Exec {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_http_path() -> String {
+    "/".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub probe: HealthProbe,
+    #[serde(default = "default_health_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default = "default_health_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Wait this long after spawn before the first probe.
+    #[serde(default)]
+    pub initial_delay_ms: u64,
+    /// Consecutive passes needed to become healthy.
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: u32,
+    /// Consecutive failures needed to become unhealthy.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+}
+
+fn default_health_interval_ms() -> u64 {
+    5000
+}
+
+fn default_health_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_success_threshold() -> u32 {
+    1
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HealthStatus {
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+pub fn default_stop_timeout_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StopSignal {
+    #[default]
+    Term,
+    Int,
+    Quit,
+    Hup,
+}
+
+impl StopSignal {
+    #[cfg(unix)]
+    pub fn as_raw(self) -> i32 {
+        match self {
+            StopSignal::Term => libc::SIGTERM,
+            StopSignal::Int => libc::SIGINT,
+            StopSignal::Quit => libc::SIGQUIT,
+            StopSignal::Hup => libc::SIGHUP,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backoff {
+    /// Always wait `delay_ms` between restarts.
+    Fixed,
+    /// Double the delay after each consecutive restart, up to `max_delay_ms`.
+    Exponential,
+}
+
+/// How an auto-restart process is respawned after it exits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub backoff: Backoff,
+    /// Fixed delay, or the first delay for exponential backoff.
+    pub delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Randomise each delay by up to this fraction (0.0 - 1.0) either way.
+    pub jitter: f64,
+    /// Restarts allowed within `retry_window_ms` before giving up (0 = unlimited).
+    pub max_retries: u32,
+    pub retry_window_ms: u64,
+    /// A run that lasts at least this long resets the backoff and retry budget.
+    pub min_uptime_ms: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            backoff: Backoff::Exponential,
+            delay_ms: 1000,
+            max_delay_ms: 30_000,
+            jitter: 0.1,
+            max_retries: 10,
+            retry_window_ms: 60_000,
+            min_uptime_ms: 5_000,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the `attempt`-th consecutive restart (1-based).
+    pub fn delay_for(&self, attempt: u32) -> std::time::Duration {
+        let base = match self.backoff {
+            Backoff::Fixed => self.delay_ms,
+            Backoff::Exponential => {
+                let factor = 1u64 << attempt.saturating_sub(1).min(32);
+                self.delay_ms.saturating_mul(factor).min(self.max_delay_ms.max(self.delay_ms))
+            }
+        };
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let ms = if jitter > 0.0 {
+            let spread = rand::random::<f64>() * 2.0 - 1.0;
+            (base as f64 * (1.0 + spread * jitter)).max(0.0) as u64
+        } else {
+            base
+        };
+        std::time::Duration::from_millis(ms)
+    }
+}
+
+/// Runs `actions` when a logged line (after redaction) matches `pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogTrigger {
+    pub pattern: String,
+    /// "stdout" or "stderr"; both when unset.
+    #[serde(default)]
+    pub stream: Option<String>,
+    pub actions: Vec<TriggerAction>,
+    /// Matches within this long of the last firing are ignored, so a noisy
+    /// log can't set off a storm of actions.
+    #[serde(default = "default_trigger_cooldown_ms")]
+    pub cooldown_ms: u64,
+}
+
+fn default_trigger_cooldown_ms() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+    /// Emit `process:trigger` with the matching line.
+    Event,
+    /// Desktop notification; the body defaults to the matching line.
+    Notify {
+        #[serde(default)]
+        message: Option<String>,
+    },
+    Restart,
+    Stop,
+    /// Count the process as ready, as a passing readiness check would.
+    MarkReady,
+    /// Run a shell command in the process's working dir, with
+    /// `PM_PROCESS_ID`, `PM_PROCESS_NAME` and `PM_TRIGGER_LINE` set.
+    Hook { command: String },
+}
+
+/// How a process's output is logged: parsing, redaction, rotation and retention.
+/// Limits of 0 are disabled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogPolicy {
+    /// Rotate the active log once it reaches this size.
+    pub max_size_mb: u64,
+    /// Rotate the active log once it is this old.
+    pub max_age_hours: u64,
+    /// Rotated segments to keep.
+    pub max_files: usize,
+    /// Delete rotated segments older than this.
+    pub retain_days: u64,
+    /// Cap on the combined size of rotated segments.
+    pub max_total_mb: u64,
+    /// Gzip segments after rotation.
+    pub compress: bool,
+    /// Index the level, message and fields of lines that are JSON objects.
+    pub parse_json: bool,
+    /// Regexes masked out of every line before it is stored or shown; with
+    /// capture groups only the groups are masked, e.g. `token=(\S+)`.
+    pub redact: Vec<String>,
+}
+
+impl Default for LogPolicy {
+    fn default() -> Self {
+        LogPolicy {
+            max_size_mb: 10,
+            max_age_hours: 24,
+            max_files: 10,
+            retain_days: 14,
+            max_total_mb: 200,
+            compress: false,
+            parse_json: false,
+            redact: Vec::new(),
+        }
+    }
+}
+
+/// Resource limits of a process. The memory and CPU thresholds apply to the
+/// whole process tree and are checked by the watchdog on every metrics
+/// sample; the rest are hard limits set at spawn, on Linux only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Resident memory of the tree.
+    pub max_memory_mb: Option<u64>,
+    /// CPU of the tree, where 100 is one core.
+    pub max_cpu_percent: Option<f32>,
+    /// How long CPU must stay above `max_cpu_percent` to count.
+    pub cpu_window_ms: u64,
+    /// What the watchdog does when a threshold is crossed.
+    pub action: LimitAction,
+    /// `RLIMIT_AS`: allocations beyond this fail.
+    pub max_address_space_mb: Option<u64>,
+    /// `RLIMIT_NOFILE`.
+    pub max_open_files: Option<u64>,
+    /// `RLIMIT_CORE`; 0 disables core dumps.
+    pub max_core_mb: Option<u64>,
+    /// Scheduling niceness, from -20 (highest priority) to 19; going below
+    /// the current value needs privileges.
+    pub nice: Option<i32>,
+    /// I/O scheduling class and priority, as set by `ionice`.
+    pub ionice: Option<IoPriority>,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_memory_mb: None,
+            max_cpu_percent: None,
+            cpu_window_ms: 60_000,
+            action: LimitAction::default(),
+            max_address_space_mb: None,
+            max_open_files: None,
+            max_core_mb: None,
+            nice: None,
+            ionice: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    /// Emit `process:limit_exceeded` only.
+    #[default]
+    Warn,
+    Restart,
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IoPriority {
+    pub class: IoClass,
+    /// 0 (highest) to 7; ignored by the idle class.
+    #[serde(default = "default_io_level")]
+    pub level: u8,
+}
+
+fn default_io_level() -> u8 {
+    4
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl IoPriority {
+    /// The `ioprio` value taken by `ioprio_set(2)`.
+    #[cfg(target_os = "linux")]
+    pub fn as_raw(self) -> i32 {
+        const IOPRIO_CLASS_SHIFT: i32 = 13;
+        let class = match self.class {
+            IoClass::Realtime => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        };
+        (class << IOPRIO_CLASS_SHIFT) | self.level.min(7) as i32
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessStatus {
     Running,
+    /// Stop signal sent; waiting for the process to exit.
+    Stopping,
     Stopped,
     Crashed,
+    /// Crashed too often within the restart window; auto-restart gave up.
+    Errored,
 }
 
 impl std::fmt::Display for ProcessStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ProcessStatus::Running => write!(f, "Running"),
+            ProcessStatus::Stopping => write!(f, "Stopping"),
             ProcessStatus::Stopped => write!(f, "Stopped"),
             ProcessStatus::Crashed => write!(f, "Crashed"),
+            ProcessStatus::Errored => write!(f, "Errored"),
         }
     }
 }
@@ -41,20 +412,327 @@ pub struct ProcessState {
     pub auto_restart: bool,
     pub auto_start: bool,
     pub working_dir: Option<String>,
+    pub env: std::collections::HashMap<String, String>,
+    pub env_file: Option<String>,
+    pub clear_env: bool,
+    pub depends_on: Vec<String>,
+    /// `None` when the process is not running or has no health checks.
+    pub health: Option<HealthStatus>,
     pub uptime_ms: u64,
     pub crash_count: u32,
+    pub workspace: Option<String>,
+}
+
+/// An open project file and the processes it declares.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceInfo {
+    pub name: String,
+    pub path: String,
+    pub process_ids: Vec<String>,
+}
+
+/// A saved copy of `processes.json` in the backups directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigBackup {
+    pub name: String,
+    pub created_ms: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    /// The process was left out.
+    Error,
+    /// The process was loaded, but may not start as expected.
+    Warning,
+}
+
+/// A problem found while loading `processes.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub severity: IssueSeverity,
+    /// Name of the process, or `#<n>` when it has none; `None` for the
+    /// document itself.
+    pub process: Option<String>,
+    /// Path of the offending field, e.g. `restart_policy.delay_ms`.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            IssueSeverity::Error => "error",
+            IssueSeverity::Warning => "warning",
+        };
+        write!(f, "{}: ", severity)?;
+        if let Some(process) = &self.process {
+            write!(f, "{}: ", process)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, "{}: ", field)?;
+        }
+        write!(f, "{}", self.message)
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// docker-compose / compose.yaml services.
+    Compose,
+    Procfile,
+    /// PM2 ecosystem file: JavaScript, JSON or YAML.
+    Pm2,
+}
+
+/// Processes added by an import, and what couldn't be translated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub format: ImportFormat,
+    pub process_ids: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A run that ended in a crash, kept for support bundles.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessMetrics {
+pub struct CrashRecord {
+    /// RFC 3339 in UTC.
+    pub timestamp: String,
+    pub run_id: u64,
+    /// `None` when killed by a signal or the process failed to spawn.
+    pub exit_code: Option<i32>,
+    pub uptime_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A descendant of a managed process and its own usage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub cmdline: String,
     pub cpu_percent: f32,
-    pub memory_mb: u64,
+    pub memory_bytes: u64,
     pub memory_percent: f32,
 }
 
+/// One reading of a process and its descendants, or a rollup: the averages over a period
+/// starting at `ts`, with its peaks in the `_max` fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSample {
+    /// Milliseconds since the Unix epoch.
+    pub ts: u64,
+    pub cpu_percent: f32,
+    pub cpu_max: f32,
+    /// Resident set size.
+    pub memory_bytes: u64,
+    pub memory_max_bytes: u64,
+    pub memory_percent: f32,
+    /// Linux only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u32>,
+    /// Linux only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_fds: Option<u32>,
+    pub disk_read_bytes_per_sec: f64,
+    pub disk_write_bytes_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetricsResolution {
+    /// Raw samples when `from` is unset or still in memory, else the finest
+    /// rollup kept that far back.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "raw")]
+    Raw,
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+/// App-wide settings, kept under `settings` in `processes.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub metrics: MetricsSettings,
+    /// Copies of `processes.json` kept in `backups/` (0 disables them).
+    pub config_backups: usize,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            metrics: MetricsSettings::default(),
+            config_backups: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// How often running processes are sampled.
+    pub interval_ms: u64,
+    /// Raw samples kept in memory per process.
+    pub raw_samples: usize,
+    /// How long 1-minute rollups are kept on disk.
+    pub minute_retention_days: u64,
+    /// How long 1-hour rollups are kept on disk.
+    pub hour_retention_days: u64,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        MetricsSettings {
+            interval_ms: 2000,
+            raw_samples: 1800,
+            minute_retention_days: 7,
+            hour_retention_days: 90,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
+    /// RFC 3339 in UTC, e.g. "2024-05-01T12:00:00.123Z".
     pub timestamp: String,
-    pub level: String, // "stdout", "stderr"
+    /// "stdout" or "stderr".
+    pub stream: String,
     pub message: String,
+    /// Run of the process that wrote the line; 0 in legacy logs.
+    #[serde(default)]
+    pub run_id: u64,
+    /// Position of the line within its run.
+    #[serde(default)]
+    pub seq: u64,
+    /// Level reported by JSON-formatted output, lowercased.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// Fields of JSON-formatted output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Byte offset of the entry within its log file.
+    #[serde(default)]
+    pub offset: u64,
+}
+
+/// A position in a process's log that stays valid across rotation and
+/// compression: a byte offset within the file whose first entry is stamped
+/// `file`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogCursor {
+    pub file: String,
+    pub offset: u64,
+}
+
+/// Selects a page of log entries. Without `after`, the page is the newest
+/// matching entries before `before` (or the end of the log).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// Page backwards from this position (exclusive).
+    pub before: Option<LogCursor>,
+    /// Page forwards from this position (inclusive).
+    pub after: Option<LogCursor>,
+    /// Earliest time to include: RFC 3339, or local time like "2024-05-01 12:00".
+    pub since: Option<String>,
+    /// Time to stop before, in the same formats as `since`.
+    pub until: Option<String>,
+    /// Only entries whose message contains this text.
+    pub search: Option<String>,
+    /// Treat `search` as a regular expression.
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Only entries from these streams; empty means all.
+    pub streams: Vec<String>,
+    /// Only JSON-formatted entries reporting one of these levels; empty means all.
+    pub levels: Vec<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogPage {
+    /// Matching entries, oldest first.
+    pub entries: Vec<LogEntry>,
+    /// Pass as `before` to fetch older entries; `None` when none are left.
+    pub before: Option<LogCursor>,
+    /// Pass as `after` to fetch newer entries, e.g. when polling a live log.
+    pub after: LogCursor,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogExportFormat {
+    /// `<timestamp> [<stream>] <message>` per line.
+    #[default]
+    Text,
+    /// The on-disk JSON records.
+    Jsonl,
+    /// timestamp, stream, run, seq, level and message columns.
+    Csv,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn restart_policy(backoff: Backoff, jitter: f64) -> RestartPolicy {
+        RestartPolicy {
+            backoff,
+            delay_ms: 1000,
+            max_delay_ms: 10_000,
+            jitter,
+            ..RestartPolicy::default()
+        }
+    }
+
+    #[test]
+    fn fixed_delay_ignores_attempt() {
+        let policy = restart_policy(Backoff::Fixed, 0.0);
+        for attempt in [0, 1, 5, 100] {
+            assert_eq!(policy.delay_for(attempt), Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn exponential_delay_doubles_up_to_max() {
+        let policy = restart_policy(Backoff::Exponential, 0.0);
+        let delays: Vec<u64> = (1..=6).map(|a| policy.delay_for(a).as_millis() as u64).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 10_000, 10_000]);
+        assert_eq!(policy.delay_for(0), Duration::from_millis(1000));
+        assert_eq!(policy.delay_for(u32::MAX), Duration::from_millis(10_000));
+    }
+
+    #[test]
+    fn max_delay_below_delay_keeps_delay() {
+        let policy = RestartPolicy {
+            max_delay_ms: 10,
+            ..restart_policy(Backoff::Exponential, 0.0)
+        };
+        assert_eq!(policy.delay_for(4), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn jitter_stays_within_fraction() {
+        let policy = restart_policy(Backoff::Fixed, 0.25);
+        for _ in 0..200 {
+            let ms = policy.delay_for(1).as_millis();
+            assert!((750..=1250).contains(&ms), "{} out of range", ms);
+        }
+        let clamped = restart_policy(Backoff::Fixed, 5.0);
+        for _ in 0..200 {
+            assert!(clamped.delay_for(1).as_millis() <= 2000);
+        }
+    }
 }
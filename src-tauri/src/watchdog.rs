@@ -0,0 +1,89 @@
+//! Soft resource limits: each metrics sample of a process tree is checked
+//! against the process's memory and CPU thresholds.
+
+use crate::types::{MetricsSample, ResourceLimits};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A threshold that has just been crossed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breach {
+    Memory { mb: u64, max_mb: u64 },
+    /// CPU stayed above the limit for the whole window.
+    Cpu { percent: f32, max_percent: f32, window: Duration },
+}
+
+impl Breach {
+    pub fn limit(&self) -> &'static str {
+        match self {
+            Breach::Memory { .. } => "memory",
+            Breach::Cpu { .. } => "cpu",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Breach::Memory { mb, max_mb } => format!("memory {} MB over the {} MB limit", mb, max_mb),
+            Breach::Cpu { percent, max_percent, window } => format!(
+                "CPU {:.0}% over the {:.0}% limit for {}s",
+                percent,
+                max_percent,
+                window.as_secs_f64()
+            ),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RunState {
+    /// When CPU last went above the limit, while it stays there.
+    cpu_over_since: Option<Instant>,
+    memory_reported: bool,
+    cpu_reported: bool,
+}
+
+/// Per-run threshold state, keyed by root pid so a restarted process
+/// starts afresh.
+#[derive(Default)]
+pub struct Watchdog {
+    runs: HashMap<u32, RunState>,
+}
+
+impl Watchdog {
+    /// Thresholds `sample` newly crosses. Each is reported once, and again
+    /// only after usage has dropped back under it.
+    pub fn check(&mut self, pid: u32, limits: &ResourceLimits, sample: &MetricsSample, now: Instant) -> Vec<Breach> {
+        let run = self.runs.entry(pid).or_default();
+        let mut breaches = Vec::new();
+
+        if let Some(max_mb) = limits.max_memory_mb {
+            let mb = sample.memory_bytes.div_ceil(1024 * 1024);
+            if mb <= max_mb {
+                run.memory_reported = false;
+            } else if !run.memory_reported {
+                run.memory_reported = true;
+                breaches.push(Breach::Memory { mb, max_mb });
+            }
+        }
+
+        if let Some(max_percent) = limits.max_cpu_percent {
+            if sample.cpu_percent <= max_percent {
+                run.cpu_over_since = None;
+                run.cpu_reported = false;
+            } else {
+                let since = *run.cpu_over_since.get_or_insert(now);
+                let window = Duration::from_millis(limits.cpu_window_ms);
+                if !run.cpu_reported && now.duration_since(since) >= window {
+                    run.cpu_reported = true;
+                    breaches.push(Breach::Cpu { percent: sample.cpu_percent, max_percent, window });
+                }
+            }
+        }
+        breaches
+    }
+
+    /// Forget runs whose root pid is no longer running.
+    pub fn retain(&mut self, running: impl Fn(u32) -> bool) {
+        self.runs.retain(|pid, _| running(*pid));
+    }
+}
@@ -0,0 +1,149 @@
+//! Project files: a `pm.toml` checked into a repository that declares its
+//! processes, opened side by side with others as a named workspace.
+//!
+//! ```toml
+//! name = "shop"                 # defaults to the directory name
+//!
+//! [env]                         # shared by every process
+//! NODE_ENV = "development"
+//!
+//! [processes.db]
+//! command = "docker"
+//! args = ["compose", "up", "postgres"]
+//! readiness = { type = "tcp", port = 5432 }
+//!
+//! [processes.api]
+//! command = "npm"
+//! args = ["run", "dev"]
+//! working_dir = "api"           # relative to this file
+//! env_file = "api/.env"
+//! depends_on = ["db"]
+//! ```
+//!
+//! Each `[processes.<key>]` table takes the fields of a process in
+//! `processes.json`. Processes are named after their key and get the id
+//! `<workspace>.<key>`, so logs and metrics carry over between opens.
+
+use crate::types::ProcessConfig;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "pm.toml";
+
+pub struct Workspace {
+    pub name: String,
+    /// The project file, absolute.
+    pub path: PathBuf,
+    pub processes: Vec<ProcessConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    name: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    processes: toml::Table,
+}
+
+/// The project file for `path`: the file itself, or `pm.toml` in it when
+/// it is a directory.
+pub fn locate(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(FILE_NAME)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Workspace and process names end up in ids and file names.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn process_id(workspace: &str, key: &str) -> String {
+    format!("{}.{}", workspace, key)
+}
+
+/// Read and resolve the project file at `path` (see [`locate`]).
+pub fn load(path: &Path) -> Result<Workspace, String> {
+    let path = std::path::absolute(locate(path)).map_err(|e| e.to_string())?;
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file: ProjectFile = toml::from_str(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+    let name = match file.name {
+        Some(name) if valid_name(&name) => name,
+        Some(name) => {
+            return Err(format!(
+                "Invalid workspace name '{}': use letters, digits, '-' and '_'",
+                name
+            ))
+        }
+        None => dir
+            .file_name()
+            .map(|n| {
+                n.to_string_lossy()
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+                    .collect()
+            })
+            .filter(|n: &String| !n.is_empty())
+            .unwrap_or_else(|| "workspace".to_string()),
+    };
+
+    let mut processes = Vec::new();
+    for (key, value) in &file.processes {
+        if !valid_name(key) {
+            return Err(format!(
+                "Invalid process name '{}': use letters, digits, '-' and '_'",
+                key
+            ));
+        }
+        let toml::Value::Table(table) = value else {
+            return Err(format!("[processes.{}] must be a table", key));
+        };
+        if table.contains_key("id") || table.contains_key("name") {
+            return Err(format!("[processes.{}]: id and name come from the key", key));
+        }
+
+        let mut table = table.clone();
+        table.insert("id".into(), process_id(&name, key).into());
+        table.insert("name".into(), key.as_str().into());
+        for (field, default) in [("auto_restart", false), ("auto_start", false)] {
+            table.entry(field).or_insert(default.into());
+        }
+        table.entry("args").or_insert(toml::Value::Array(Vec::new()));
+        let mut config: ProcessConfig = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("[processes.{}]: {}", key, e))?;
+
+        let relative_to_file = |p: &str| dir.join(p).to_string_lossy().to_string();
+        config.working_dir = Some(match &config.working_dir {
+            Some(working_dir) => relative_to_file(working_dir),
+            None => dir.to_string_lossy().to_string(),
+        });
+        config.env_file = config.env_file.as_deref().map(relative_to_file);
+
+        let mut env = file.env.clone();
+        env.extend(config.env.take().unwrap_or_default());
+        config.env = Some(env);
+
+        config.depends_on = config
+            .depends_on
+            .iter()
+            .map(|dep| {
+                if file.processes.contains_key(dep) {
+                    Ok(process_id(&name, dep))
+                } else {
+                    Err(format!("[processes.{}] depends on unknown process '{}'", key, dep))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        processes.push(config);
+    }
+
+    Ok(Workspace { name, path, processes })
+}
@@ -1,10 +1,25 @@
 import { defineStore } from "pinia";
 import { reactive, ref } from "vue";
-import type { Process, LogEntry, ProcessMetrics } from "@/types/process";
+import type { Process, LogEntry, ProcessMetrics, Workspace, ImportReport, ConfigIssue, ConfigBackup } from "@/types/process";
+
+interface UsagePayload {
+  cpu_percent: number;
+  memory_bytes: number;
+  memory_percent: number;
+}
+
+export interface MetricsPayload {
+  id: string;
+  metrics: UsagePayload;
+  children: (UsagePayload & { pid: number; parent_pid: number | null; name: string; cmdline: string })[];
+}
+
+const toMb = (bytes: number) => Math.ceil(bytes / (1024 * 1024));
 import { invoke } from "@tauri-apps/api/core";
 
 export const useProcessStore = defineStore("process", () => {
   const processes = ref<Process[]>([]);
+  const workspaces = ref<Workspace[]>([]);
   const selectedProcessId = ref<string | null>(null);
   const logs = reactive<Record<string, LogEntry[]>>({});
   const metrics = reactive<Record<string, ProcessMetrics>>({});
@@ -24,6 +39,7 @@ export const useProcessStore = defineStore("process", () => {
         cpuPercent: 0,
         memoryMb: 0,
         memoryPercent: 0,
+        children: [],
       }
     );
   };
@@ -62,6 +78,17 @@ export const useProcessStore = defineStore("process", () => {
     }
   };
 
+  const importProcesses = async (path: string, format?: ImportReport["format"], runJs = false) => {
+    try {
+      const report: ImportReport = await invoke("import_processes", { path, format: format ?? null, runJs });
+      await loadProcesses();
+      return report;
+    } catch (error) {
+      console.error("Failed to import processes:", error);
+      throw error;
+    }
+  };
+
   const removeProcess = async (id: string) => {
     try {
       await invoke("remove_process", { processId: id });
@@ -150,7 +177,7 @@ export const useProcessStore = defineStore("process", () => {
       processes.value = result;
       result.forEach((p) => {
         if (!logs[p.id]) logs[p.id] = [];
-        if (!metrics[p.id]) metrics[p.id] = { cpuPercent: 0, memoryMb: 0, memoryPercent: 0 };
+        if (!metrics[p.id]) metrics[p.id] = { cpuPercent: 0, memoryMb: 0, memoryPercent: 0, children: [] };
       });
     } catch (error) {
       console.error("Failed to load processes:", error);
@@ -162,12 +189,12 @@ export const useProcessStore = defineStore("process", () => {
     try {
       const result: Array<{
         timestamp: string;
-        level: string;
+        stream: string;
         message: string;
       }> = await invoke("get_logs", { processId: id });
       logs[id] = result.map((log) => ({
         timestamp: log.timestamp,
-        level: (log.level === "stdout" ? "stdout" : "stderr") as
+        level: (log.stream === "stdout" ? "stdout" : "stderr") as
           | "stdout"
           | "stderr",
         message: log.message,
@@ -196,25 +223,28 @@ export const useProcessStore = defineStore("process", () => {
     }
   };
 
-  const updateMetrics = async (id: string) => {
-    try {
-      const result: { cpu_percent: number; memory_mb: number; memory_percent: number } = await invoke(
-        "get_metrics",
-        { processId: id }
-      );
-      metrics[id] = {
-        cpuPercent: result.cpu_percent,
-        memoryMb: result.memory_mb,
-        memoryPercent: result.memory_percent,
-      };
-    } catch (error) {
-      console.error("Failed to get metrics:", error);
-    }
+  // Fed by `process:metrics` events pushed by the backend sampler.
+  const setMetrics = ({ id, metrics: total, children }: MetricsPayload) => {
+    metrics[id] = {
+      cpuPercent: total.cpu_percent,
+      memoryMb: toMb(total.memory_bytes),
+      memoryPercent: total.memory_percent,
+      children: children.map((c) => ({
+        pid: c.pid,
+        parentPid: c.parent_pid ?? undefined,
+        name: c.name,
+        cmdline: c.cmdline,
+        cpuPercent: c.cpu_percent,
+        memoryMb: toMb(c.memory_bytes),
+        memoryPercent: c.memory_percent,
+      })),
+    };
   };
 
-  const saveConfig = async () => {
+  /** `force` overwrites changes another instance made to the file. */
+  const saveConfig = async (force = false) => {
     try {
-      await invoke("save_config");
+      await invoke("save_config", { force });
     } catch (error) {
       console.error("Failed to save config:", error);
       throw error;
@@ -227,14 +257,106 @@ export const useProcessStore = defineStore("process", () => {
       processes.value = result;
       result.forEach((p) => {
         if (!logs[p.id]) logs[p.id] = [];
-        if (!metrics[p.id]) metrics[p.id] = { cpuPercent: 0, memoryMb: 0, memoryPercent: 0 };
+        if (!metrics[p.id]) metrics[p.id] = { cpuPercent: 0, memoryMb: 0, memoryPercent: 0, children: [] };
       });
+      await loadWorkspaces();
     } catch (error) {
       console.error("Failed to load config:", error);
       throw error;
     }
   };
 
+  const loadWorkspaces = async () => {
+    try {
+      workspaces.value = await invoke("list_workspaces");
+    } catch (error) {
+      console.error("Failed to load workspaces:", error);
+      throw error;
+    }
+  };
+
+  const openWorkspace = async (path: string) => {
+    try {
+      const workspace: Workspace = await invoke("open_workspace", { path });
+      await loadProcesses();
+      await loadWorkspaces();
+      return workspace;
+    } catch (error) {
+      console.error("Failed to open workspace:", error);
+      throw error;
+    }
+  };
+
+  const closeWorkspace = async (name: string) => {
+    try {
+      await invoke("close_workspace", { name });
+      const closed = processes.value.filter((p) => p.workspace === name).map((p) => p.id);
+      if (selectedProcessId.value && closed.includes(selectedProcessId.value)) {
+        selectedProcessId.value = null;
+      }
+      await loadProcesses();
+      await loadWorkspaces();
+    } catch (error) {
+      console.error("Failed to close workspace:", error);
+      throw error;
+    }
+  };
+
+  const workspaceUp = async (name: string) => {
+    try {
+      await invoke("workspace_up", { name });
+      await loadProcesses();
+    } catch (error) {
+      console.error("Failed to start workspace:", error);
+      throw error;
+    }
+  };
+
+  const workspaceDown = async (name: string) => {
+    try {
+      await invoke("workspace_down", { name });
+      await loadProcesses();
+    } catch (error) {
+      console.error("Failed to stop workspace:", error);
+      throw error;
+    }
+  };
+
+  const checkConfig = async (): Promise<ConfigIssue[]> => {
+    try {
+      return await invoke("check_config");
+    } catch (error) {
+      console.error("Failed to check config:", error);
+      throw error;
+    }
+  };
+
+  const listBackups = async (): Promise<ConfigBackup[]> => {
+    try {
+      return await invoke("list_config_backups");
+    } catch (error) {
+      console.error("Failed to list backups:", error);
+      throw error;
+    }
+  };
+
+  const restoreBackup = async (name: string) => {
+    try {
+      const result: Process[] = await invoke("restore_config_backup", { name });
+      processes.value = result;
+      result.forEach((p) => {
+        if (!logs[p.id]) logs[p.id] = [];
+        if (!metrics[p.id]) metrics[p.id] = { cpuPercent: 0, memoryMb: 0, memoryPercent: 0, children: [] };
+      });
+      if (selectedProcessId.value && !result.some((p) => p.id === selectedProcessId.value)) {
+        selectedProcessId.value = null;
+      }
+    } catch (error) {
+      console.error("Failed to restore backup:", error);
+      throw error;
+    }
+  };
+
   const setAutoStart = async (enable: boolean) => {
     try {
       await invoke("set_auto_start", { enable });
@@ -264,9 +386,11 @@ export const useProcessStore = defineStore("process", () => {
 
   return {
     processes,
+    workspaces,
     selectedProcessId,
     selectedProcess,
     addProcess,
+    importProcesses,
     removeProcess,
     startProcess,
     stopProcess,
@@ -278,11 +402,19 @@ export const useProcessStore = defineStore("process", () => {
     addLog,
     clearLogs,
     getProcessMetrics,
-    updateMetrics,
+    setMetrics,
     saveConfig,
     loadConfig,
+    checkConfig,
+    listBackups,
+    restoreBackup,
     setAutoStart,
     startAll,
     stopAll,
+    loadWorkspaces,
+    openWorkspace,
+    closeWorkspace,
+    workspaceUp,
+    workspaceDown,
   };
 });
@@ -3,13 +3,27 @@ export interface Process {
   name: string;
   command: string;
   args: string[];
-  status: "Running" | "Stopped" | "Crashed";
+  status: "Running" | "Stopping" | "Stopped" | "Crashed" | "Errored";
   pid?: number;
   autoRestart: boolean;
   autoStart: boolean;
   workingDir?: string;
+  env?: Record<string, string>;
+  envFile?: string;
+  clearEnv?: boolean;
+  dependsOn?: string[];
+  health?: "Starting" | "Healthy" | "Unhealthy" | null;
   uptimeMs: number;
   crashCount: number;
+  /** Name of the workspace the process was loaded from, if any. */
+  workspace?: string;
+}
+
+/** A project file opened as a workspace. */
+export interface Workspace {
+  name: string;
+  path: string;
+  processIds: string[];
 }
 
 export interface LogEntry {
@@ -18,10 +32,22 @@ export interface LogEntry {
   message: string;
 }
 
+export interface ProcessUsage {
+  pid: number;
+  parentPid?: number;
+  name: string;
+  cmdline: string;
+  cpuPercent: number;
+  memoryMb: number;
+  memoryPercent: number;
+}
+
+/** Totals over the process and its descendants. */
 export interface ProcessMetrics {
   cpuPercent: number;
   memoryMb: number;
   memoryPercent: number;
+  children: ProcessUsage[];
 }
 
 export interface ProcessConfig {
@@ -32,4 +58,29 @@ export interface ProcessConfig {
   autoRestart: boolean;
   autoStart: boolean;
   env?: Record<string, string>;
+  envFile?: string;
+  clearEnv?: boolean;
+  dependsOn?: string[];
+}
+
+/** Processes added by an import, and what couldn't be translated. */
+export interface ImportReport {
+  format: "compose" | "procfile" | "pm2";
+  processIds: string[];
+  warnings: string[];
+}
+
+/** A problem found while loading the saved config. */
+export interface ConfigIssue {
+  severity: "error" | "warning";
+  process: string | null;
+  field: string | null;
+  message: string;
+}
+
+/** A saved copy of processes.json. */
+export interface ConfigBackup {
+  name: string;
+  createdMs: number;
+  size: number;
 }